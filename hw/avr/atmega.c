@@ -75,6 +75,7 @@ static const peripheral_cfg dev168_328[PERIFMAX] = {
     [GPIOD]         = {  0x29 },
     [GPIOC]         = {  0x26 },
     [GPIOB]         = {  0x23 },
+    [TWI]           = {  0xb8 },
 }, dev1280_2560[PERIFMAX] = {
     [USART3]        = { 0x130, POWER1, 2 },
     [TIMER5]        = { 0x120, POWER1, 5, 0x73, 0x3a, true },
@@ -148,6 +149,7 @@ static const uint8_t irq168_328[IRQ_COUNT] = {
     [USART0_RXC_IRQ]        = 19,
     [USART0_DRE_IRQ]        = 20,
     [USART0_TXC_IRQ]        = 21,
+    [TWI_IRQ]               = 25,
 }, irq1280_2560[IRQ_COUNT] = {
     [TIMER2_COMPA_IRQ]      = 14,
     [TIMER2_COMPB_IRQ]      = 15,
@@ -353,11 +355,11 @@ static void atmega_realize(DeviceState *dev, Error **errp)
 
     /* TWI/I2C */
     object_initialize_child(OBJECT(dev), "TWI_I2C", &s->twi, "TWI_I2C");
+    object_property_set_uint(OBJECT(&s->twi), "cpu-frequency-hz",
+                             s->xtal_freq_hz, &error_abort);
     sbd = SYS_BUS_DEVICE(&s->twi);
     sysbus_realize(sbd, &error_abort);
-    //sysbus_connect_irq(sbd, 40, s->twi.irq);
-    // TODO: should i add a sub-region instead?
-    sysbus_mmio_map_overlap(sbd, 0, OFFSET_DATA + 0x0b8, 1);
+    sysbus_mmio_map(sbd, 0, OFFSET_DATA + mc->dev[TWI].addr);
     connect_peripheral_irq(mc, sbd, 0, cpudev, TWI_IRQ);
 
     //create_unimplemented_device("avr-twi",          OFFSET_DATA + 0x0b8, 6);
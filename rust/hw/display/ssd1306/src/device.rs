@@ -1,28 +1,53 @@
 use core::ptr::NonNull;
 use std::{
     ffi::{c_void, CStr},
+    pin::Pin,
     ptr::{addr_of, addr_of_mut},
 };
 
 use qemu_api::{
     bindings::{
-        error_fatal, qdev_new, qdev_prop_set_chr, qemu_console_surface, qemu_irq,
-        sysbus_connect_irq, sysbus_mmio_map, sysbus_realize_and_unref, Chardev, DisplaySurface,
-        Error, I2CSlave,
+        dpy_gfx_update_full, error_fatal, qdev_new, qdev_prop_set_chr, qemu_console_surface,
+        qemu_irq, sysbus_connect_irq, sysbus_mmio_map, sysbus_realize_and_unref,
+        timer_expire_time_ns, Chardev, DisplaySurface, Error, I2CSlave, I2C_START_SEND,
     },
     c_str,
+    cell::{BqlCell, BqlRefCell},
     i2cslave::I2CSlaveImpl,
     qdev::{DeviceImpl, DeviceState, Property, ResetType, ResettablePhasesImpl},
     qom::{IsA, Object, ObjectImpl, ObjectType, ParentField},
     qom_isa,
+    timer::{Timer, CLOCK_VIRTUAL},
     vmstate::VMStateDescription,
 };
 use qemu_api_macros::Object;
 
-const WIDTH: usize = 128;
-const HEIGHT: usize = 64;
+/// Number of segment outputs (GDDRAM columns) driven by the controller.
+const SEGMENTS: usize = 128;
 const MAGNIFY: usize = 1;
-const I2C_ADDRESS: u8 = 0x3d;
+/// Number of COM lines (GDDRAM rows) driven by the controller.
+const COM_LINES: usize = 64;
+/// Panel sizes (width, height) of the SSD1306 modules we emulate.
+const PANEL_GEOMETRIES: [(u32, u32); 3] = [(128, 64), (128, 32), (96, 16)];
+pub const DEFAULT_WIDTH: u32 = 128;
+pub const DEFAULT_HEIGHT: u32 = 64;
+/// The address selected by the SA0 pin, modules are usually strapped low
+/// (0x3C) and can be changed to 0x3D.
+const I2C_ADDRESSES: [u8; 2] = [0x3c, 0x3d];
+
+/// Duration of one frame with the RESET oscillator frequency, clock divide
+/// ratio and multiplex ratio (about 105 Hz).
+const FRAME_PERIOD_NS: u64 = 9_500_000;
+
+/// Number of frames between two scroll steps, indexed by the 3-bit time
+/// interval parameter of the scroll setup commands.
+const SCROLL_INTERVAL_FRAMES: [u64; 8] = [5, 64, 128, 256, 3, 4, 25, 2];
+
+/// Number of steps of the contrast ramp in the fade out and blinking modes.
+const FADE_STEPS: u8 = 16;
+
+/// The largest number of parameters taken by a command (0x26/0x27).
+const MAX_PARAMETERS: usize = 6;
 
 #[inline(always)]
 /// TODO: document this, and check if bindgen supports exporting C macros
@@ -30,28 +55,45 @@ const fn _pixman_format_reshift(val: u32, ofs: u32, num: u32) -> u32 {
     ((val >> ofs) & ((1 << num) - 1)) << ((val >> 22) & 3)
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
+#[repr(u8)]
 /// The addressing mode used for the GDDRAM.
+///
+/// The discriminants are the A[1:0] values of the 0x20 command.
 pub enum MemoryAddressingMode {
     /// After the display RAM is read/written, the column address pointer is
     /// increased automatically by 1. **If the column address pointer
     /// reaches column end address, the column address pointer is
     /// reset to column start address and page address pointer is increased by
     /// 1**
-    Horizontal,
+    Horizontal = 0,
     /// After the display RAM is read/written, the page address pointer is
     /// increased automatically by 1. If the page address pointer reaches
     /// the page end address, the page address pointer is reset
     /// to page start address and column address pointer is increased by 1
-    Vertical,
+    Vertical = 1,
     /// After the display RAM is read/written, the column address pointer is
     /// increased automatically by 1. **If the column address pointer
     /// reaches column end address, the column address pointer is
     /// reset to column start address and page address pointer is not changed**
-    Page,
+    Page = 2,
+}
+
+impl TryFrom<u8> for MemoryAddressingMode {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Horizontal),
+            1 => Ok(Self::Vertical),
+            2 => Ok(Self::Page),
+            _ => Err(()),
+        }
+    }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug)]
+#[repr(u8)]
 /// The control byte (D/C# bit).
 ///
 /// This determines whether the current I2C transfer contains a command or
@@ -59,20 +101,29 @@ pub enum MemoryAddressingMode {
 pub enum DataMode {
     /// The following byte(s) are interpreted as commands.
     /// Corresponds to D/C# = 0 in the control byte.
-    Command,
+    Command = 0,
     /// The following byte(s) are stored in the GDDRAM.
     /// Corresponds to D/C# = 1 in the control byte.
-    Data,
+    Data = 1,
 }
 
-#[repr(C)]
-#[derive(Debug, Object, qemu_api_macros::offsets)]
-pub struct SSD1306State {
-    pub parent_obj: ParentField<I2CSlave>,
-    pub i2c: I2CSlave,
-    pub console: *mut qemu_api::bindings::QemuConsole,
-    pub someprop: char,
+impl TryFrom<u8> for DataMode {
+    type Error = ();
 
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Command),
+            1 => Ok(Self::Data),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The controller state that is changed by the commands and data sent over
+/// I2C.
+#[repr(C)]
+#[derive(Debug, qemu_api_macros::offsets)]
+pub struct SSD1306Registers {
     /// Indicates whether the command is still waiting for additional
     /// parameters.
     ///
@@ -86,11 +137,13 @@ pub struct SSD1306State {
     /// A/B/C/D/E/F
     pub in_command: bool,
 
-    /// This holds the parameters for the current command
-    pub parameters: Vec<u8>,
+    /// This holds the parameters for the current command, the first
+    /// `parameter_count` are valid. See [`parameters`](Self::parameters).
+    pub parameter_buffer: [u8; MAX_PARAMETERS],
+    pub parameter_count: u8,
 
     pub command: u8,
-    pub params_number: usize,
+    pub params_number: u8,
     /// Graphic Display Data RAM (GDDRAM).
     ///
     /// The GDDRAM is a bit mapped static RAM holding the bit pattern to be
@@ -99,7 +152,7 @@ pub struct SSD1306State {
     ///
     /// Each byte in the array directly corresponds to a column in a specific
     /// page.
-    pub gddram: [u8; 128 * 8],
+    pub gddram: [u8; SEGMENTS * (COM_LINES / 8)],
     pub memory_addressing_mode: MemoryAddressingMode,
     pub column_start_address: u8,
     pub column_end_address: u8,
@@ -107,20 +160,163 @@ pub struct SSD1306State {
     pub page_start_address: u8,
     pub page_end_address: u8,
     pub page_address_pointer: u8,
-    /// Whether we have received D/C yet for the current transfer
-    /// Note: each transfer beings with a D/C
+    /// Whether we have received the control byte (Co and D/C#) for the next
+    /// byte(s).
+    ///
+    /// Note: each transfer begins with a control byte, and another one follows
+    /// every byte sent after a control byte with Co = 1.
     pub recieved_dc: bool,
+    /// The Co (continuation) bit of the last control byte. When set, only one
+    /// byte follows the control byte.
+    pub continuation: bool,
     pub display_enabled: bool,
     pub multiplex_ratio: u8,
     pub display_offset: u8,
     pub display_start_line: u8,
     pub segment_remap_enabled: bool,
     pub com_remap_enabled: bool,
+    /// COM pins hardware configuration (0xDA A[4]), alternative instead of
+    /// sequential.
+    pub com_pins_alternative: bool,
+    /// COM pins hardware configuration (0xDA A[5]), swap the left and right
+    /// halves of the COM pins.
+    pub com_left_right_remap: bool,
     pub force_display_on: bool,
     /// An inverted display will treat 1 in the RAM as OFF, and 0 as ON.
     pub display_inverted: bool,
-    /// The data mode for the current transfer
+    /// Contrast (0x81), the segment output current.
+    pub contrast: u8,
+    /// Pre-charge period (0xD9) in DCLKs, phase 1 in A[3:0] and phase 2 in
+    /// A[7:4].
+    pub precharge_period: u8,
+    /// Whether the internal charge pump is enabled (0x8D A[2]).
+    pub charge_pump_enabled: bool,
+    /// Whether the module generates VCC with the charge pump instead of
+    /// taking it from an external supply. Set from the `internal-vcc`
+    /// property, and kept across RESET.
+    pub internal_vcc: bool,
+    /// The data mode set by the last control byte.
     pub data_mode: DataMode,
+
+    /// Whether scrolling was activated with 0x2F.
+    pub scroll_active: bool,
+    /// Whether a scroll setup command was received, activating without one
+    /// is invalid.
+    pub scroll_configured: bool,
+    /// Scroll towards column 0 (0x27/0x2A) instead of column 127 (0x26/0x29).
+    pub scroll_left: bool,
+    pub scroll_start_page: u8,
+    pub scroll_end_page: u8,
+    /// Number of frames between two scroll steps.
+    pub scroll_interval_frames: u64,
+    /// Rows moved per scroll step in the vertical scroll area, 0 for the
+    /// horizontal only scroll commands.
+    pub scroll_vertical_step: u8,
+    /// Current vertical displacement of the vertical scroll area.
+    pub scroll_vertical_offset: u8,
+    /// Number of rows in the top fixed area (0xA3 A[5:0]).
+    pub vertical_scroll_fixed_rows: u8,
+    /// Number of rows in the vertical scroll area (0xA3 B[6:0]).
+    pub vertical_scroll_rows: u8,
+
+    /// Fade out and blinking setting (0x23 A[5:0]), A[5:4] is the mode and
+    /// A[3:0] the time interval of each fade step.
+    pub fade_setting: u8,
+    /// Current step of the fade ramp, from `FADE_STEPS` (the contrast as set)
+    /// down to 0 (dark).
+    pub fade_level: u8,
+    /// Whether the blinking ramp is going back up.
+    pub fade_rising: bool,
+    /// Zoom in (0xD6 A[0]), every scan line of the upper half is output on
+    /// two COM lines.
+    pub zoom_in: bool,
+
+    /// `memory_addressing_mode` as migrated, see
+    /// [`pre_save`](Self::pre_save).
+    pub migration_memory_addressing_mode: u8,
+    /// `data_mode` as migrated, see [`pre_save`](Self::pre_save).
+    pub migration_data_mode: u8,
+}
+
+impl Default for SSD1306Registers {
+    /// The state after RESET, as listed in the command table of the
+    /// datasheet.
+    fn default() -> Self {
+        Self {
+            in_command: false,
+            parameter_buffer: [0; MAX_PARAMETERS],
+            parameter_count: 0,
+            command: 0,
+            params_number: 0,
+            // The GDDRAM content is not defined after RESET, start blank.
+            gddram: [0; SEGMENTS * (COM_LINES / 8)],
+            memory_addressing_mode: MemoryAddressingMode::Page,
+            column_start_address: 0,
+            column_end_address: (SEGMENTS - 1) as u8,
+            column_address_pointer: 0,
+            page_start_address: 0,
+            page_end_address: (COM_LINES / 8 - 1) as u8,
+            page_address_pointer: 0,
+            recieved_dc: false,
+            continuation: false,
+            display_enabled: false,
+            multiplex_ratio: (COM_LINES - 1) as u8,
+            display_offset: 0,
+            display_start_line: 0,
+            segment_remap_enabled: false,
+            com_remap_enabled: false,
+            com_pins_alternative: true,
+            com_left_right_remap: false,
+            force_display_on: false,
+            display_inverted: false,
+            contrast: 0x7f,
+            precharge_period: 0x22,
+            charge_pump_enabled: false,
+            internal_vcc: false,
+            data_mode: DataMode::Command,
+            scroll_active: false,
+            scroll_configured: false,
+            scroll_left: false,
+            scroll_start_page: 0,
+            scroll_end_page: 0,
+            scroll_interval_frames: SCROLL_INTERVAL_FRAMES[0],
+            scroll_vertical_step: 0,
+            scroll_vertical_offset: 0,
+            vertical_scroll_fixed_rows: 0,
+            vertical_scroll_rows: COM_LINES as u8,
+            fade_setting: 0,
+            fade_level: FADE_STEPS,
+            fade_rising: false,
+            zoom_in: false,
+            migration_memory_addressing_mode: 0,
+            migration_data_mode: 0,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Object, qemu_api_macros::offsets)]
+pub struct SSD1306State {
+    pub parent_obj: ParentField<I2CSlave>,
+    pub console: *mut qemu_api::bindings::QemuConsole,
+    pub someprop: char,
+    /// Panel width in pixels, one of the `PANEL_GEOMETRIES`.
+    pub width: BqlCell<u32>,
+    /// Panel height in pixels, one of the `PANEL_GEOMETRIES`.
+    pub height: BqlCell<u32>,
+    /// The panel is powered by the charge pump, and stays dark until it is
+    /// enabled.
+    pub internal_vcc: bool,
+    pub scroll_timer: Timer,
+    /// Fires for every step of the fade out and blinking contrast ramp.
+    pub fade_timer: Timer,
+    pub registers: BqlRefCell<SSD1306Registers>,
+    /// Expiry time of `scroll_timer` as migrated, `u64::MAX` if it is not
+    /// pending.
+    pub migration_deadline_ns: BqlCell<u64>,
+    /// Expiry time of `fade_timer` as migrated, `u64::MAX` if it is not
+    /// pending.
+    pub migration_fade_deadline_ns: BqlCell<u64>,
 }
 
 unsafe impl ObjectType for SSD1306State {
@@ -155,8 +351,8 @@ impl ResettablePhasesImpl for SSD1306State {
 
 pub extern "C" fn ssd1306_update_display(opaque: *mut std::os::raw::c_void) {
     unsafe {
-        let mut state = NonNull::new_unchecked(opaque.cast::<SSD1306State>());
-        state.as_mut().update_display();
+        let state = NonNull::new_unchecked(opaque.cast::<SSD1306State>());
+        state.as_ref().update_display();
     }
 }
 
@@ -181,14 +377,38 @@ impl SSD1306State {
     /// values with the sole exception of `parent_obj`.
     pub unsafe fn init(&mut self) {
         println!("init ssd1306");
-        // TODO: move to reset
-        self.parameters = Vec::with_capacity(10);
-        self.gddram = [0; HEIGHT * (WIDTH / 8)];
         unsafe {
-            println!("setting ssd1306 address");
-            qemu_api::bindings::i2c_slave_set_address(&mut self.i2c as *mut _, I2C_ADDRESS);
+            addr_of_mut!(self.registers).write(BqlRefCell::new(SSD1306Registers::default()));
+        }
+        unsafe {
+            // The `address` property is inherited from the I2C slave, default it
+            // to SA0 strapped low.
+            qemu_api::bindings::i2c_slave_set_address(&mut *self.parent_obj, I2C_ADDRESSES[0]);
         }
 
+        // SAFETY: the timer is only used as part of SSD1306State, which is always
+        // pinned, and outlives it.
+        let scroll_timer = unsafe { Pin::new_unchecked(&mut *addr_of_mut!(self.scroll_timer)) };
+        scroll_timer.init_full(
+            None,
+            CLOCK_VIRTUAL,
+            Timer::NS,
+            0,
+            Self::scroll_timer_expired,
+            unsafe { &*addr_of!(*self) },
+        );
+
+        // SAFETY: see `scroll_timer`.
+        let fade_timer = unsafe { Pin::new_unchecked(&mut *addr_of_mut!(self.fade_timer)) };
+        fade_timer.init_full(
+            None,
+            CLOCK_VIRTUAL,
+            Timer::NS,
+            0,
+            Self::fade_timer_expired,
+            unsafe { &*addr_of!(*self) },
+        );
+
         unsafe {
             // TODO: this was done quickly, make sure it is safe later
             self.console = qemu_api::bindings::graphic_console_init(
@@ -197,31 +417,48 @@ pub unsafe fn init(&mut self) {
                 addr_of_mut!(SSD1306_OPS),
                 (self as *mut Self).cast::<c_void>(),
             );
-            qemu_api::bindings::qemu_console_resize(
-                self.console,
-                (WIDTH * MAGNIFY) as i32,
-                (HEIGHT * MAGNIFY) as i32,
-            );
         }
     }
     pub fn realize(&self) {
         println!("realize ssd1306");
+        if !I2C_ADDRESSES.contains(&self.parent_obj.address) {
+            // TODO: Add error binding: warn_report()
+            eprintln!(
+                "ssd1306: address {:#x} can not be selected with SA0",
+                self.parent_obj.address
+            );
+        }
+        let geometry = (self.width.get(), self.height.get());
+        if !PANEL_GEOMETRIES.contains(&geometry) {
+            // TODO: Add error binding: error_setg()
+            eprintln!(
+                "ssd1306: unsupported panel size {}x{}, using {}x{}",
+                geometry.0, geometry.1, DEFAULT_WIDTH, DEFAULT_HEIGHT
+            );
+            self.width.set(DEFAULT_WIDTH);
+            self.height.set(DEFAULT_HEIGHT);
+        }
+
+        self.registers.borrow_mut().internal_vcc = self.internal_vcc;
+
+        // The console is created in `init`, but the panel size is only known
+        // once the properties are set.
+        unsafe {
+            qemu_api::bindings::qemu_console_resize(
+                self.console,
+                (self.width.get() as usize * MAGNIFY) as i32,
+                (self.height.get() as usize * MAGNIFY) as i32,
+            );
+        }
     }
 
     pub fn reset_hold(&self, _type: ResetType) {
-        println!("reset ssd1306");
-        // TODO: implement all comments with "RESET"
-        // TODO: USE CELL/OR CHECK WHY RESET DOES NOT TAKE A MUTABLE REF
-        //unsafe {
-        //    let a = addr_of!(self) as *mut Self;
-        //    let a = &mut *a as &mut Self;
-        //    a.in_command = false;
-        //    a.parameters = Vec::with_capacity(10);
-        //    a.memory_addressing_mode = MemoryAddressingMode::Page;
-        //}
-    }
-
-    pub fn update_display(&mut self) {
+        self.registers.borrow_mut().reset();
+        self.scroll_timer.delete();
+        self.fade_timer.delete();
+    }
+
+    pub fn update_display(&self) {
         let surface: &DisplaySurface = unsafe {
             NonNull::new(qemu_console_surface(self.console))
                 .expect("display surface pointer is null")
@@ -251,39 +488,273 @@ pub fn update_display(&mut self) {
         //};
         //let _dest_width = dest_width * MAGNIFY;
 
+        let registers = self.registers.borrow();
+        let (width, height) = (self.width.get() as usize, self.height.get() as usize);
         let data = unsafe { qemu_api::bindings::pixman_image_get_data(surface.image) };
-        for y in 0..HEIGHT {
-            for x in 0..WIDTH {
-                let page = y / 8;
-                let byte_index = page * WIDTH + x;
-                let bit_position = y % 8;
-                let pixel_val = (self.gddram[byte_index] >> bit_position) & 1;
-
-                let pixel_offset = y * WIDTH + x; // Map (x, y) to framebuffer index
+        let intensity = u32::from(registers.pixel_intensity());
+        let lit = 0xff000000 | (intensity << 16) | (intensity << 8) | intensity;
+        for y in 0..height {
+            for x in 0..width {
+                let pixel_offset = y * width + x; // Map (x, y) to framebuffer index
                 unsafe {
-                    *data.add(pixel_offset) = if pixel_val == 0 {
-                        0 // Black pixel
+                    *data.add(pixel_offset) = if registers.pixel_lit(x, y, height) {
+                        lit // Grey in ARGB
                     } else {
-                        0xffffffff // Color in ARGB (Blue pixel) 
+                        0 // Black pixel
                     };
                 }
             }
         }
+
+        unsafe {
+            dpy_gfx_update_full(self.console);
+        }
     }
 
-    /// # Safety
-    pub unsafe fn i2c_recv(&mut self) -> u8 {
-        println!("I2C RECV");
-        b'a'
+    /// Data is **read** from the device.
+    pub fn i2c_recv(&self) -> u8 {
+        self.registers.borrow().status()
+    }
+
+    /// Data is **sent** to the device.
+    pub fn i2c_send(&self, data: u8) -> i32 {
+        let mut registers = self.registers.borrow_mut();
+        if registers.i2c_send(data) {
+            self.update_timers(&registers);
+        }
+        0 // success
+    }
+
+    pub fn i2c_event(&self, event: u32) -> i32 {
+        self.registers.borrow_mut().i2c_event(event);
+        0
+    }
+
+    pub fn pre_save(&self) {
+        self.registers.borrow_mut().pre_save();
+        // SAFETY: the timer was initialized in `init`.
+        let deadline = unsafe { timer_expire_time_ns(self.scroll_timer.as_mut_ptr()) };
+        self.migration_deadline_ns.set(deadline);
+        let fade_deadline = unsafe { timer_expire_time_ns(self.fade_timer.as_mut_ptr()) };
+        self.migration_fade_deadline_ns.set(fade_deadline);
+    }
+
+    pub fn post_load(&self, _version_id: u32) -> Result<(), ()> {
+        self.registers.borrow_mut().post_load()?;
+        match self.migration_deadline_ns.get() {
+            u64::MAX => self.scroll_timer.delete(),
+            deadline => self.scroll_timer.modify(deadline),
+        }
+        match self.migration_fade_deadline_ns.get() {
+            u64::MAX => self.fade_timer.delete(),
+            deadline => self.fade_timer.modify(deadline),
+        }
+        Ok(())
+    }
+
+    fn scroll_timer_expired(&self) {
+        let mut registers = self.registers.borrow_mut();
+        registers.scroll_step();
+        self.update_scroll_timer(&registers);
+    }
+
+    fn fade_timer_expired(&self) {
+        let mut registers = self.registers.borrow_mut();
+        registers.fade_step();
+        self.update_fade_timer(&registers);
+    }
+
+    /// Update the timers after a command changed the scroll or fade settings.
+    fn update_timers(&self, registers: &SSD1306Registers) {
+        self.update_scroll_timer(registers);
+        self.update_fade_timer(registers);
+    }
+
+    /// Arm the fade timer for the next step of the contrast ramp, or stop it
+    /// once the ramp is over.
+    fn update_fade_timer(&self, registers: &SSD1306Registers) {
+        if registers.fade_active() {
+            self.fade_timer
+                .modify(CLOCK_VIRTUAL.get_ns() + registers.fade_step_period_ns());
+        } else {
+            self.fade_timer.delete();
+        }
+    }
+
+    /// Arm the scroll timer for the next step, or stop it if scrolling was
+    /// deactivated.
+    fn update_scroll_timer(&self, registers: &SSD1306Registers) {
+        if registers.scroll_active {
+            self.scroll_timer
+                .modify(CLOCK_VIRTUAL.get_ns() + registers.scroll_step_period_ns());
+        } else {
+            self.scroll_timer.delete();
+        }
+    }
+}
+
+impl SSD1306Registers {
+    /// Restore the RESET value of every setting and abort the command that is
+    /// being received.
+    pub fn reset(&mut self) {
+        *self = Self {
+            internal_vcc: self.internal_vcc,
+            ..Self::default()
+        };
+    }
+
+    /// Whether the panel has a supply voltage, modules with an internal VCC
+    /// need the charge pump.
+    pub const fn panel_powered(&self) -> bool {
+        !self.internal_vcc || self.charge_pump_enabled
+    }
+
+    /// The parameters received for the current command.
+    pub fn parameters(&self) -> &[u8] {
+        &self.parameter_buffer[..usize::from(self.parameter_count)]
+    }
+
+    /// Store the fields that have no `VMState` in their migrated form.
+    pub fn pre_save(&mut self) {
+        self.migration_memory_addressing_mode = self.memory_addressing_mode as u8;
+        self.migration_data_mode = self.data_mode as u8;
+    }
+
+    /// Restore the fields that have no `VMState` from their migrated form,
+    /// and check the ones that index the GDDRAM and the parameter buffer.
+    pub fn post_load(&mut self) -> Result<(), ()> {
+        self.memory_addressing_mode =
+            MemoryAddressingMode::try_from(self.migration_memory_addressing_mode)?;
+        self.data_mode = DataMode::try_from(self.migration_data_mode)?;
+        let columns = [
+            self.column_start_address,
+            self.column_end_address,
+            self.column_address_pointer,
+        ];
+        let pages = [
+            self.page_start_address,
+            self.page_end_address,
+            self.page_address_pointer,
+            self.scroll_start_page,
+            self.scroll_end_page,
+        ];
+        if usize::from(self.parameter_count) > MAX_PARAMETERS
+            || usize::from(self.params_number) > MAX_PARAMETERS
+            // A pending command still expects at least one parameter.
+            || (self.in_command && self.parameter_count >= self.params_number)
+            || columns
+                .iter()
+                .any(|&column| usize::from(column) >= SEGMENTS)
+            || pages.iter().any(|&page| usize::from(page) >= COM_LINES / 8)
+            || self.scroll_start_page > self.scroll_end_page
+            || !SCROLL_INTERVAL_FRAMES.contains(&self.scroll_interval_frames)
+            || usize::from(self.vertical_scroll_fixed_rows) + usize::from(self.vertical_scroll_rows)
+                > COM_LINES
+            || self.fade_level > FADE_STEPS
+        {
+            return Err(());
+        }
+        Ok(())
+    }
+
+    /// Returns whether the pixel at `(x, y)` on a panel with `height` rows is
+    /// lit.
+    ///
+    /// This follows the path of the data through the controller: the COM
+    /// line driving row `y` (COM pins configuration and scan direction), the
+    /// scan line it outputs (display offset, multiplex ratio and zoom in),
+    /// the GDDRAM row read for that scan line (vertical scroll area and
+    /// display start line) and the column driving segment `x` (segment
+    /// re-map).
+    pub fn pixel_lit(&self, x: usize, y: usize, height: usize) -> bool {
+        if !self.display_enabled || !self.panel_powered() {
+            return false;
+        }
+
+        let mux = usize::from(self.multiplex_ratio);
+        let com = self.com_line(y, height);
+        let com = if self.com_remap_enabled {
+            // Scan from COM[N-1] to COM0
+            (mux + COM_LINES - com) % COM_LINES
+        } else {
+            com
+        };
+        let scan_line = (com + usize::from(self.display_offset)) % COM_LINES;
+        if scan_line > mux {
+            // COM outputs beyond the multiplex ratio are not driven.
+            return false;
+        }
+        // Zoom in outputs every scan line of the upper half twice.
+        let scan_line = if self.zoom_in {
+            scan_line / 2
+        } else {
+            scan_line
+        };
+        if self.force_display_on {
+            return true;
+        }
+
+        let row = (self.scrolled_row(scan_line) + usize::from(self.display_start_line)) % COM_LINES;
+        let column = if self.segment_remap_enabled {
+            SEGMENTS - 1 - x
+        } else {
+            x
+        };
+        let bit = (self.gddram[(row / 8) * SEGMENTS + column] >> (row % 8)) & 1;
+        (bit == 1) != self.display_inverted
+    }
+
+    /// Returns the intensity (0 to 255) of a lit pixel.
+    ///
+    /// The datasheet gives no brightness curve, the contrast (segment current)
+    /// is mapped linearly from half to full intensity. A phase 2 of the
+    /// pre-charge period shorter than its RESET value of 2 DCLKs does not
+    /// fully charge the pixels, which halves the intensity.
+    ///
+    /// The fade out and blinking modes scale the result down to 0 with the
+    /// fade level.
+    pub fn pixel_intensity(&self) -> u8 {
+        let mut intensity = 0x80 + u16::from(self.contrast) / 2;
+        if (self.precharge_period >> 4) < 2 {
+            intensity /= 2;
+        }
+        (intensity * u16::from(self.fade_level) / u16::from(FADE_STEPS)) as u8
+    }
+
+    /// Returns the COM line connected to row `y` of a panel with `height`
+    /// rows.
+    ///
+    /// 128x64 modules route their rows to the COM pins in the alternative
+    /// (interleaved) order, the smaller modules use the first `height` pins
+    /// in sequence. The picture is only correct when 0xDA matches the wiring
+    /// of the panel (0x12 and 0x02 respectively).
+    fn com_line(&self, y: usize, height: usize) -> usize {
+        let half = COM_LINES / 2;
+        let pin = if height == COM_LINES {
+            if y < half {
+                2 * y
+            } else {
+                2 * (y - half) + 1
+            }
+        } else {
+            y
+        };
+
+        match (self.com_pins_alternative, self.com_left_right_remap) {
+            (false, false) => pin,
+            (false, true) => (pin + half) % COM_LINES,
+            (true, false) => pin / 2 + if pin % 2 == 0 { 0 } else { half },
+            (true, true) => pin / 2 + if pin % 2 == 0 { half } else { 0 },
+        }
     }
 
     /// Returns the number of parameters a command expects.
     ///
     /// For example, if the command 0x26 is recieved, we expect 6 bytes to be
     /// sent over I2C.
-    const fn get_number_of_parameters(&self) -> usize {
+    const fn get_number_of_parameters(&self) -> u8 {
         match self.command {
-            0x81 | 0x1 | 0x20 | 0xd3 | 0xda | 0xd5 | 0xd9 | 0xdb | 0xa8 | 0x8d => 1,
+            0x81 | 0x20 | 0xd3 | 0xda | 0xd5 | 0xd9 | 0xdb | 0xa8 | 0x8d | 0x23 | 0xd6 => 1,
             0x26 | 0x27 => 6,
             0x29 | 0x2a => 5,
             0xa3 | 0x21 | 0x22 => 2,
@@ -291,20 +762,12 @@ const fn get_number_of_parameters(&self) -> usize {
         }
     }
 
-    // When this is called, the command parameters are in `self.parameters`
+    // When this is called, all the command parameters are in
+    // `self.parameters()`. A command whose transfer ended early stays pending
+    // until the next transfer sends the remaining parameters.
     //
-    // Note: it is possible that the I2C transaction was ended before recieving all
-    // parameters, so the vector may be incomplete.
-    fn command(&mut self) {
-        dbg!(self.display_inverted);
-        dbg!(self.com_remap_enabled);
-        dbg!(self.segment_remap_enabled);
-        dbg!(self.multiplex_ratio);
-        dbg!(self.column_start_address);
-        dbg!(self.column_end_address);
-        dbg!(self.page_start_address);
-        dbg!(self.page_end_address);
-
+    // Returns `true` if the timers must be updated.
+    fn command(&mut self) -> bool {
         //println!("command: {}", self.command);
         match self.command {
             // --- Fundamental Commands ---
@@ -315,8 +778,8 @@ fn command(&mut self) {
             // Reset: 0x7f
             0x81 => {
                 // TODO: expects an A[7:0] byte
-                if let Some(_contrast) = self.parameters.first() {
-                    // TODO: Should i emulate contrast?
+                if let Some(contrast) = self.parameters().first().copied() {
+                    self.contrast = contrast;
                 } else {
                     eprintln!("Expected parameter `contrast`");
                 }
@@ -344,11 +807,88 @@ fn command(&mut self) {
             }
             0xaf => {
                 self.display_enabled = true;
+                if !self.panel_powered() {
+                    // TODO: Add error binding: qemu_log_mask(LOG_GUEST_ERROR, ...)
+                    eprintln!("ssd1306: display turned on without enabling the charge pump (0x8D)");
+                }
             }
             // --- Scrolling Commands ---
             //
-            // TODO TODO TODO
+            // Continuous Horizontal Scroll Setup
             //
+            // A and E are dummy bytes, B[2:0] is the start page, C[2:0] the
+            // time interval between steps and D[2:0] the end page.
+            0x26 | 0x27 => {
+                self.setup_scroll(0);
+            }
+            // Continuous Vertical and Horizontal Scroll Setup
+            //
+            // Same as 0x26/0x27, with E[5:0] the vertical scrolling offset.
+            0x29 | 0x2a => {
+                self.setup_scroll(self.parameters()[4] & 0b00111111);
+            }
+            // Deactivate scroll
+            0x2e => {
+                self.scroll_active = false;
+                self.scroll_vertical_offset = 0;
+                return true;
+            }
+            // Activate scroll
+            0x2f => {
+                if !self.scroll_configured {
+                    eprintln!("Activating scroll without a scroll setup command");
+                    return false;
+                }
+                self.scroll_active = true;
+                return true;
+            }
+            // Set Vertical Scroll Area
+            //
+            // A[5:0] is the number of rows in the top fixed area, B[6:0] the
+            // number of rows in the scroll area. RESET: A = 0, B = 64.
+            0xa3 => {
+                let fixed = self.parameters()[0] & 0b00111111;
+                let rows = self.parameters()[1] & 0b01111111;
+                if usize::from(fixed) + usize::from(rows) > COM_LINES {
+                    eprintln!("invalid vertical scroll area: {} + {}", fixed, rows);
+                    return false;
+                }
+                self.vertical_scroll_fixed_rows = fixed;
+                self.vertical_scroll_rows = rows;
+            }
+            // --- Advance Graphic Commands ---
+            //
+            // Set Fade Out and Blinking
+            //
+            // A[5:4]=00b(RESET), Disable fade out and blinking
+            // A[5:4]=10b, Enable fade out, the contrast decreases down to
+            // dark and stays there
+            // A[5:4]=11b, Enable blinking, the contrast decreases and
+            // increases again repeatedly
+            // A[3:0] is the time interval of each fade step, from 8 frames
+            // (0000b) to 128 frames (1111b).
+            0x23 => {
+                let setting = self.parameters()[0];
+                if setting & 0b00110000 == 0b00010000 {
+                    eprintln!("invalid fade out and blinking mode: {:#x}", setting);
+                    return false;
+                }
+                self.fade_setting = setting & 0b00111111;
+                self.fade_level = FADE_STEPS;
+                self.fade_rising = false;
+                return true;
+            }
+            // Set Zoom In
+            //
+            // A[0]=0b(RESET), Disable zoom in
+            // A[0]=1b, Enable zoom in, the COM pins must use the alternative
+            // configuration
+            0xd6 => {
+                self.zoom_in = self.parameters()[0] & 1 != 0;
+                if self.zoom_in && !self.com_pins_alternative {
+                    eprintln!("zoom in requires the alternative COM pins configuration");
+                }
+            }
             // --- Addressing Setting Commands ---
             //
             // Set the lower nibble of the column start address
@@ -356,25 +896,23 @@ fn command(&mut self) {
             // as data bits. The initial display line register is
             // reset to 0000b after RESET.
             0x00..=0x0f => {
-                todo!();
+                self.column_address_pointer =
+                    (self.column_address_pointer & 0xf0) | (self.command & 0x0f);
             }
             // Set the higher nibble of the column start address
             // register for Page Addressing Mode using X[3:0]
             // as data bits. The initial display line register is
             // reset to 0000b after RESET.
             0x10..=0x1f => {
-                let high_nibble = self.command & 0x0f;
-                println!(
-                    "setting high nibble for colummn start address: {:b}",
-                    high_nibble
-                );
-                println!("{:#?}", self.memory_addressing_mode);
-                todo!();
+                // X[3] would address column 128 and above, which does not exist.
+                let high_nibble = self.command & 0x07;
+                self.column_address_pointer =
+                    (self.column_address_pointer & 0x0f) | (high_nibble << 4);
             }
             // Set Memory Addressing Mode
             0x20 => {
                 // the 2 LSBs are the mode.
-                if let Some(data) = self.parameters.first() {
+                if let Some(data) = self.parameters().first().copied() {
                     match data & 0x3 {
                         // Horizontal Addressing Mode
                         0x0 => {
@@ -399,21 +937,23 @@ fn command(&mut self) {
             0x21 => {
                 if matches!(self.memory_addressing_mode, MemoryAddressingMode::Page) {
                     eprintln!("Setting the column address is not allowed in page addressing mode");
-                    return;
+                    return false;
                 }
 
-                if let Some(address) = self.parameters.first() {
+                if let Some(address) = self.parameters().first().copied() {
                     self.column_start_address = address & 0b01111111;
                     println!("set column start address: {}", self.column_start_address);
                 } else {
                     eprintln!("expected column start address");
                 };
-                if let Some(address) = self.parameters.get(1) {
+                if let Some(address) = self.parameters().get(1).copied() {
                     self.column_end_address = address & 0b01111111;
                     println!("set column end address: {}", self.column_end_address);
                 } else {
                     eprintln!("expected column end address");
                 };
+                // The column address pointer is also reset to the start address.
+                self.column_address_pointer = self.column_start_address;
             }
             // Set Page Address
             //
@@ -424,26 +964,29 @@ fn command(&mut self) {
                         "setting the page start and end address is not supported in Page memory \
                          addressing."
                     );
-                    return;
+                    return false;
                 }
-                if let Some(start) = self.parameters.first() {
+                if let Some(start) = self.parameters().first().copied() {
                     self.page_start_address = start & 0b00000111;
                 } else {
                     eprintln!("expected parameter `page_start_address`");
                 }
-                if let Some(end) = self.parameters.get(1) {
+                if let Some(end) = self.parameters().get(1).copied() {
                     self.page_end_address = end & 0b00000111;
                 } else {
                     eprintln!("expected parameter `page_start_address`");
                 }
+                // The page address pointer is also reset to the start address.
+                self.page_address_pointer = self.page_start_address;
             }
             // Set Page Start Address for Page Addressing Mode.
             //
             // Set GDDRAM Page Start Address (PAGE0~PAGE7) for Page Addressing Mode using X[2:0].
             //
             // Note: This command is only for page addressing mode
+            // The page address pointer is reset to PAGE0 after RESET.
             0xb0..=0xb7 => {
-                todo!();
+                self.page_address_pointer = self.command & 0b00000111;
             }
             // --- Hardware Configuration (Panel resolution & layout related) Commands ---
             //
@@ -468,9 +1011,10 @@ fn command(&mut self) {
             // Set MUX ratio to N+1 MUX
             0xa8 => {
                 // TODO: RESET = 0b111111 (63d)
-                if let Some(ratio) = self.parameters.first() {
+                if let Some(ratio) = self.parameters().first().copied() {
                     let ratio = ratio & 0b00111111;
-                    if !(16..=63).contains(&ratio) {
+                    // 15 (16MUX) is the smallest ratio, used by 96x16 panels.
+                    if !(15..=63).contains(&ratio) {
                         eprintln!("unsupported multiplex ratio: {}", ratio);
                     } else {
                         println!("setting mux ratio to: {}", ratio);
@@ -494,7 +1038,7 @@ fn command(&mut self) {
                 // TODO: expects A[5:0]
                 // Set vertical shift by COM from 0d~63d The value is reset to
                 // 00h after RESET.
-                if let Some(offset) = self.parameters.first() {
+                if let Some(offset) = self.parameters().first().copied() {
                     let offset = offset & 0b00111111;
                     if !(0..=63).contains(&offset) {
                         eprintln!("unsupported display offset: {}", offset);
@@ -508,20 +1052,22 @@ fn command(&mut self) {
             }
             // Set COM Pins Hardware Configuration
             0xda => {
-                // TODO: expects A[5:4]
                 // A[4]=0b, Sequential COM pin configuration
                 // A[4]=1b(RESET), Alternative COM pin
                 // configuration
                 // A[5]=0b(RESET), Disable COM Left/Right
                 // remap
                 // A[5]=1b, Enable COM Left/Right remap
+                let config = self.parameters()[0];
+                self.com_pins_alternative = config & 0x10 != 0;
+                self.com_left_right_remap = config & 0x20 != 0;
             }
             // --- Timing & Driving Scheme Setting Commands ---
             //
             // Set Display Clock Divide Ratio/Oscillator Frequency
             0xd5 => {
                 // TODO: expects A[7:0]
-                if let Some(_ratio) = self.parameters.first() {
+                if let Some(_ratio) = self.parameters().first().copied() {
                     // NOOP
                     // TODO: remove this print
                     println!("Setting Clock Divide Ratio")
@@ -531,8 +1077,14 @@ fn command(&mut self) {
             }
             // Set Pre-charge Period
             0xd9 => {
-                if let Some(_period) = self.parameters.first() {
-                    // NOOP
+                // A[3:0] is phase 1 and A[7:4] phase 2, 0 is invalid for both.
+                // RESET = 0x22
+                if let Some(period) = self.parameters().first().copied() {
+                    if period & 0x0f == 0 || period & 0xf0 == 0 {
+                        eprintln!("invalid pre-charge period: {:#x}", period);
+                    } else {
+                        self.precharge_period = period;
+                    }
                 } else {
                     eprintln!("Expected parameter `period`");
                 }
@@ -541,9 +1093,13 @@ fn command(&mut self) {
             0xdb => {
                 // NOOP
             }
+            // Charge Pump Setting
+            //
+            // A[2]=0b(RESET), Disable charge pump
+            // A[2]=1b, Enable charge pump during display on
             0x8d => {
-                if let Some(charge_pump) = self.parameters.first() {
-                    println!("setting charge pump: {}", charge_pump);
+                if let Some(charge_pump) = self.parameters().first().copied() {
+                    self.charge_pump_enabled = charge_pump & 0b00000100 != 0;
                 } else {
                     eprintln!("Expected parameter `charge pump`");
                 }
@@ -554,16 +1110,120 @@ fn command(&mut self) {
                 eprintln!("Unknown command: {:x}", self.command);
             }
         }
+        false
+    }
+
+    /// Store the parameters shared by all scroll setup commands.
+    fn setup_scroll(&mut self, vertical_step: u8) {
+        let parameters = self.parameters();
+        let (start, interval, end) = (
+            parameters[1] & 0b111,
+            parameters[2] & 0b111,
+            parameters[3] & 0b111,
+        );
+        if end < start {
+            eprintln!("scroll end page {} is before start page {}", end, start);
+            return;
+        }
+
+        self.scroll_left = matches!(self.command, 0x27 | 0x2a);
+        self.scroll_start_page = start;
+        self.scroll_end_page = end;
+        self.scroll_interval_frames = SCROLL_INTERVAL_FRAMES[interval as usize];
+        self.scroll_vertical_step = vertical_step;
+        self.scroll_configured = true;
+    }
+
+    /// Virtual time between two scroll steps.
+    pub const fn scroll_step_period_ns(&self) -> u64 {
+        self.scroll_interval_frames * FRAME_PERIOD_NS
+    }
+
+    /// Move the scrolled area by one step, called from the scroll timer.
+    ///
+    /// Like the hardware, horizontal scrolling rotates the GDDRAM content of
+    /// the scrolled pages, vertical scrolling only displaces the rows of the
+    /// vertical scroll area when rendering.
+    pub fn scroll_step(&mut self) {
+        if !self.scroll_active {
+            return;
+        }
+
+        for page in self.scroll_start_page..=self.scroll_end_page {
+            let start = page as usize * SEGMENTS;
+            let row = &mut self.gddram[start..start + SEGMENTS];
+            if self.scroll_left {
+                row.rotate_left(1);
+            } else {
+                row.rotate_right(1);
+            }
+        }
+
+        if self.scroll_vertical_step != 0 && self.vertical_scroll_rows != 0 {
+            self.scroll_vertical_offset =
+                ((u16::from(self.scroll_vertical_offset) + u16::from(self.scroll_vertical_step))
+                    % u16::from(self.vertical_scroll_rows)) as u8;
+        }
+    }
+
+    /// Whether the contrast ramp of the fade out or blinking mode is running.
+    pub const fn fade_active(&self) -> bool {
+        match self.fade_setting >> 4 {
+            0b10 => self.fade_level > 0,
+            0b11 => true,
+            _ => false,
+        }
+    }
+
+    /// Virtual time between two steps of the fade ramp.
+    pub fn fade_step_period_ns(&self) -> u64 {
+        8 * (u64::from(self.fade_setting & 0x0f) + 1) * FRAME_PERIOD_NS
     }
 
+    /// Move the contrast ramp by one step, called from the fade timer.
+    ///
+    /// Fading out stops at level 0, blinking turns around at both ends of
+    /// the ramp.
+    pub fn fade_step(&mut self) {
+        if !self.fade_active() {
+            return;
+        }
+
+        self.fade_level = if self.fade_rising {
+            (self.fade_level + 1).min(FADE_STEPS)
+        } else {
+            self.fade_level.saturating_sub(1)
+        };
+        if self.fade_level == 0 || self.fade_level == FADE_STEPS {
+            self.fade_rising = self.fade_level == 0;
+        }
+    }
+
+    /// Map a display row to the GDDRAM row shown on it, taking the vertical
+    /// scroll area into account.
+    fn scrolled_row(&self, row: usize) -> usize {
+        let top = usize::from(self.vertical_scroll_fixed_rows);
+        let rows = usize::from(self.vertical_scroll_rows);
+        if rows == 0 || !(top..top + rows).contains(&row) {
+            return row;
+        }
+        top + (row - top + usize::from(self.scroll_vertical_offset)) % rows
+    }
+
+    /// Store `data` at the current page/column pointers and advance them
+    /// according to the memory addressing mode.
     pub fn write_gddram(&mut self, data: u8) {
+        // 128, not column_end_address
+        let index =
+            self.page_address_pointer as usize * SEGMENTS + self.column_address_pointer as usize;
+        if let Some(byte) = self.gddram.get_mut(index) {
+            *byte = data;
+        } else {
+            eprintln!("GDDRAM write out of bounds: {}", index);
+        }
+
         match self.memory_addressing_mode {
             MemoryAddressingMode::Horizontal => {
-                // 128, not column_end_address
-                let index = self.page_address_pointer as usize * 128_usize
-                    + self.column_address_pointer as usize;
-                self.gddram[index] = data;
-
                 self.column_address_pointer += 1;
                 if self.column_address_pointer > self.column_end_address {
                     self.column_address_pointer = self.column_start_address;
@@ -574,59 +1234,102 @@ pub fn write_gddram(&mut self, data: u8) {
                 }
             }
             MemoryAddressingMode::Vertical => {
-                todo!()
+                self.page_address_pointer += 1;
+                if self.page_address_pointer > self.page_end_address {
+                    self.page_address_pointer = self.page_start_address;
+                    self.column_address_pointer += 1;
+                    if self.column_address_pointer > self.column_end_address {
+                        self.column_address_pointer = self.column_start_address;
+                    }
+                }
             }
             MemoryAddressingMode::Page => {
-                todo!()
+                self.column_address_pointer += 1;
+                if self.column_address_pointer > self.column_end_address {
+                    self.column_address_pointer = self.column_start_address;
+                }
             }
         }
     }
 
-    /// Data is **sent** to the device.
-    /// # Safety
-    pub unsafe fn i2c_send(&mut self, data: u8) -> i32 {
+    /// The status register, read over I2C.
+    ///
+    /// D[6] is set while the display is OFF, the other bits are not
+    /// described in the datasheet and read as 0.
+    pub const fn status(&self) -> u8 {
+        if self.display_enabled {
+            0
+        } else {
+            0b01000000
+        }
+    }
+
+    /// Handle a byte received over I2C.
+    ///
+    /// Returns `true` if the timers must be updated.
+    pub fn i2c_send(&mut self, data: u8) -> bool {
         if !self.recieved_dc {
+            // Control byte: Co D/C# 0 0 0 0 0 0
             self.recieved_dc = true;
+            self.continuation = data & 0b10000000 != 0;
             self.data_mode = if data & 0b01000000 == 0 {
                 DataMode::Command
             } else {
                 DataMode::Data
             };
-            return 0; // drop the first data which is D/C
+            return false; // drop the control byte
         }
 
-        if matches!(self.data_mode, DataMode::Data) {
-            self.write_gddram(data);
-            return 0;
+        if self.continuation {
+            // Co = 1, the byte after this one is a control byte again.
+            self.recieved_dc = false;
         }
 
+        match self.data_mode {
+            DataMode::Data => {
+                self.write_gddram(data);
+                false
+            }
+            DataMode::Command => self.command_byte(data),
+        }
+    }
+
+    /// Handle a command byte, either a command or one of its parameters.
+    ///
+    /// Returns `true` if the timers must be updated.
+    fn command_byte(&mut self, data: u8) -> bool {
         if self.in_command {
             // `data` is a parameter for the current command
-            self.parameters.push(data);
-            if self.parameters.len() == self.params_number {
+            self.parameter_buffer[usize::from(self.parameter_count)] = data;
+            self.parameter_count += 1;
+            if self.parameter_count == self.params_number {
                 self.in_command = false;
                 // all parameters are recieved, run the command.
-                self.command();
+                return self.command();
             }
         } else {
             self.command = data;
             self.in_command = true;
-            self.parameters.clear();
+            self.parameter_count = 0;
             self.params_number = self.get_number_of_parameters();
             if self.params_number == 0 {
                 self.in_command = false;
-                self.command();
+                return self.command();
             }
         }
 
-        0 // success
+        false
     }
-    pub fn i2c_event(&mut self, event: u32) -> i32 {
-        if event == 1 {
-            // new transfer, wait for D/C
+
+    /// Handle the start or end of an I2C transfer.
+    ///
+    /// A command that is still missing parameters stays pending until the
+    /// next transfer, reads of the status register leave it untouched.
+    pub fn i2c_event(&mut self, event: u32) {
+        if event == I2C_START_SEND {
+            // new transfer, wait for the control byte
             self.recieved_dc = false;
         }
-        0
     }
 }
 
@@ -636,10 +1339,9 @@ pub fn i2c_event(&mut self, event: u32) -> i32 {
 pub unsafe extern "C" fn ssd1306_i2c_recv(dev: *mut I2CSlave) -> u8 {
     unsafe {
         assert!(!dev.is_null());
-        let mut state = NonNull::new_unchecked(dev.cast::<SSD1306State>());
-        state.as_mut().i2c_recv();
+        let state = NonNull::new_unchecked(dev.cast::<SSD1306State>());
+        state.as_ref().i2c_recv()
     }
-    1
 }
 
 /// # Safety
@@ -648,8 +1350,8 @@ pub fn i2c_event(&mut self, event: u32) -> i32 {
 pub unsafe extern "C" fn ssd1306_i2c_send(dev: *mut I2CSlave, data: u8) -> i32 {
     unsafe {
         assert!(!dev.is_null());
-        let mut state = NonNull::new_unchecked(dev.cast::<SSD1306State>());
-        state.as_mut().i2c_send(data)
+        let state = NonNull::new_unchecked(dev.cast::<SSD1306State>());
+        state.as_ref().i2c_send(data)
     }
 }
 
@@ -658,8 +1360,8 @@ pub fn i2c_event(&mut self, event: u32) -> i32 {
 /// We expect the FFI user of this function to pass a valid pointer.
 pub unsafe extern "C" fn ssd1306_i2c_event(dev: *mut I2CSlave, event: u32) -> i32 {
     unsafe {
-        let mut state = NonNull::new(dev.cast::<SSD1306State>()).expect("I2Cslave pointer is null");
-        state.as_mut().i2c_event(event)
+        let state = NonNull::new(dev.cast::<SSD1306State>()).expect("I2Cslave pointer is null");
+        state.as_ref().i2c_event(event)
     }
 }
 
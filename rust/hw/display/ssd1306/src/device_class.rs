@@ -1,8 +1,14 @@
+use std::{
+    os::raw::{c_int, c_void},
+    ptr::NonNull,
+};
+
 use qemu_api::{
-    bindings::*, c_str, vmstate_fields, vmstate_i2c_slave, vmstate_unused, zeroable::Zeroable,
+    bindings::*, c_str, cell::BqlRefCell, vmstate_fields, vmstate_i2c_slave, vmstate_of,
+    vmstate_struct, zeroable::Zeroable,
 };
 
-use crate::device::SSD1306State;
+use crate::device::{SSD1306Registers, SSD1306State, DEFAULT_HEIGHT, DEFAULT_WIDTH};
 
 qemu_api::declare_properties! {
     SSD1306_PROPERTIES,
@@ -13,19 +19,111 @@
         unsafe { &qdev_prop_chr },
         CharBackend
     ),
+    qemu_api::define_property!(
+        c_str!("width"),
+        SSD1306State,
+        width,
+        unsafe { &qdev_prop_uint32 },
+        u32,
+        default = DEFAULT_WIDTH
+    ),
+    qemu_api::define_property!(
+        c_str!("height"),
+        SSD1306State,
+        height,
+        unsafe { &qdev_prop_uint32 },
+        u32,
+        default = DEFAULT_HEIGHT
+    ),
+    qemu_api::define_property!(
+        c_str!("internal-vcc"),
+        SSD1306State,
+        internal_vcc,
+        unsafe { &qdev_prop_bool },
+        bool,
+        default = false
+    ),
+}
+
+extern "C" fn ssd1306_pre_save(opaque: *mut c_void) -> c_int {
+    let state = NonNull::new(opaque).unwrap().cast::<SSD1306State>();
+    unsafe { state.as_ref().pre_save() };
+    0
+}
+
+extern "C" fn ssd1306_post_load(opaque: *mut c_void, version_id: c_int) -> c_int {
+    let state = NonNull::new(opaque).unwrap().cast::<SSD1306State>();
+    let result = unsafe { state.as_ref().post_load(version_id as u32) };
+    if result.is_err() {
+        -1
+    } else {
+        0
+    }
 }
 
+static VMSTATE_SSD1306_REGS: VMStateDescription = VMStateDescription {
+    name: c_str!("ssd1306/regs").as_ptr(),
+    version_id: 1,
+    minimum_version_id: 1,
+    fields: vmstate_fields! {
+        vmstate_of!(SSD1306Registers, in_command),
+        vmstate_of!(SSD1306Registers, parameter_buffer),
+        vmstate_of!(SSD1306Registers, parameter_count),
+        vmstate_of!(SSD1306Registers, command),
+        vmstate_of!(SSD1306Registers, params_number),
+        vmstate_of!(SSD1306Registers, gddram),
+        vmstate_of!(SSD1306Registers, migration_memory_addressing_mode),
+        vmstate_of!(SSD1306Registers, column_start_address),
+        vmstate_of!(SSD1306Registers, column_end_address),
+        vmstate_of!(SSD1306Registers, column_address_pointer),
+        vmstate_of!(SSD1306Registers, page_start_address),
+        vmstate_of!(SSD1306Registers, page_end_address),
+        vmstate_of!(SSD1306Registers, page_address_pointer),
+        vmstate_of!(SSD1306Registers, recieved_dc),
+        vmstate_of!(SSD1306Registers, continuation),
+        vmstate_of!(SSD1306Registers, display_enabled),
+        vmstate_of!(SSD1306Registers, multiplex_ratio),
+        vmstate_of!(SSD1306Registers, display_offset),
+        vmstate_of!(SSD1306Registers, display_start_line),
+        vmstate_of!(SSD1306Registers, segment_remap_enabled),
+        vmstate_of!(SSD1306Registers, com_remap_enabled),
+        vmstate_of!(SSD1306Registers, com_pins_alternative),
+        vmstate_of!(SSD1306Registers, com_left_right_remap),
+        vmstate_of!(SSD1306Registers, force_display_on),
+        vmstate_of!(SSD1306Registers, display_inverted),
+        vmstate_of!(SSD1306Registers, contrast),
+        vmstate_of!(SSD1306Registers, precharge_period),
+        vmstate_of!(SSD1306Registers, charge_pump_enabled),
+        vmstate_of!(SSD1306Registers, migration_data_mode),
+        vmstate_of!(SSD1306Registers, scroll_active),
+        vmstate_of!(SSD1306Registers, scroll_configured),
+        vmstate_of!(SSD1306Registers, scroll_left),
+        vmstate_of!(SSD1306Registers, scroll_start_page),
+        vmstate_of!(SSD1306Registers, scroll_end_page),
+        vmstate_of!(SSD1306Registers, scroll_interval_frames),
+        vmstate_of!(SSD1306Registers, scroll_vertical_step),
+        vmstate_of!(SSD1306Registers, scroll_vertical_offset),
+        vmstate_of!(SSD1306Registers, vertical_scroll_fixed_rows),
+        vmstate_of!(SSD1306Registers, vertical_scroll_rows),
+        vmstate_of!(SSD1306Registers, fade_setting),
+        vmstate_of!(SSD1306Registers, fade_level),
+        vmstate_of!(SSD1306Registers, fade_rising),
+        vmstate_of!(SSD1306Registers, zoom_in),
+    },
+    ..Zeroable::ZERO
+};
+
 pub static VMSTATE_SSD1306: VMStateDescription = VMStateDescription {
     name: c_str!("ssd1306").as_ptr(),
-    // TODO: whats the version?
-    version_id: 2,
-    minimum_version_id: 2,
-    //post_load: Some(pl011_post_load),
-    post_load: None,
+    version_id: 3,
+    minimum_version_id: 3,
+    pre_save: Some(ssd1306_pre_save),
+    post_load: Some(ssd1306_post_load),
     fields: vmstate_fields! {
-        vmstate_unused!(core::mem::size_of::<u32>()),
-        vmstate_i2c_slave!(i2c, SSD1306State),
-        //vmstate_uint32!(flags, SSD1306State),
+        vmstate_i2c_slave!(parent_obj, SSD1306State),
+        vmstate_struct!(SSD1306State, registers, &VMSTATE_SSD1306_REGS, BqlRefCell<SSD1306Registers>),
+        vmstate_of!(SSD1306State, migration_deadline_ns),
+        vmstate_of!(SSD1306State, migration_fade_deadline_ns),
     },
     ..Zeroable::ZERO
 };
@@ -1,19 +1,36 @@
-use core::ptr::NonNull;
+use qemu_api::bindings::{I2C_FINISH, I2C_START_RECV, I2C_START_SEND};
+use ssd1306::device::{MemoryAddressingMode, SSD1306Registers};
 
-use ssd1306::device::SSD1306State;
+/// Returns a controller in its RESET state, receiving commands in a write
+/// transfer.
+fn command_mode() -> SSD1306Registers {
+    let mut ssd1306 = SSD1306Registers::default();
+    ssd1306.i2c_event(I2C_START_SEND);
+    ssd1306.i2c_send(0x00); // control byte, D/C# = 0
+    ssd1306
+}
+
+/// Writes `data` to the GDDRAM in its own transfer, then starts another
+/// transfer for commands.
+fn write_data(ssd1306: &mut SSD1306Registers, data: &[u8]) {
+    ssd1306.i2c_event(I2C_START_SEND);
+    ssd1306.i2c_send(0x40); // control byte, D/C# = 1
+    for &byte in data {
+        ssd1306.i2c_send(byte);
+    }
+    ssd1306.i2c_event(I2C_FINISH);
+    ssd1306.i2c_event(I2C_START_SEND);
+    ssd1306.i2c_send(0x00); // control byte, D/C# = 0
+}
 
 #[test]
 fn it_collects_command_parameters() {
-    let ssd1306: &mut SSD1306State = unsafe {
-        let state =
-            std::alloc::alloc(std::alloc::Layout::new::<SSD1306State>()).cast::<SSD1306State>();
-        NonNull::new_unchecked(state).as_mut()
-    };
+    let mut ssd1306 = command_mode();
 
-    unsafe {
+    {
         ssd1306.i2c_send(0x26); // A command that expects 6 parameters
         assert_eq!(ssd1306.command, 0x26);
-        assert_eq!(ssd1306.parameters.len(), 0);
+        assert_eq!(ssd1306.parameters().len(), 0);
 
         ssd1306.i2c_send(2);
         ssd1306.i2c_send(3);
@@ -23,40 +40,468 @@ fn it_collects_command_parameters() {
         ssd1306.i2c_send(6);
         ssd1306.i2c_send(7);
         assert_eq!(ssd1306.in_command, false); // all command parameters were sent.
-        assert_eq!(ssd1306.parameters.len(), 6);
-        assert_eq!(ssd1306.parameters, [2, 3, 4, 5, 6]);
+        assert_eq!(ssd1306.parameters().len(), 6);
+        assert_eq!(ssd1306.parameters(), [2, 3, 4, 5, 6, 7]);
 
         ssd1306.i2c_send(0x81);
         assert_eq!(ssd1306.in_command, true); // we haven't sent all 6 params yet
         ssd1306.i2c_send(1);
         assert_eq!(ssd1306.in_command, false);
-        assert_eq!(ssd1306.parameters.len(), 1);
-        assert_eq!(ssd1306.parameters, [1]);
+        assert_eq!(ssd1306.parameters().len(), 1);
+        assert_eq!(ssd1306.parameters(), [1]);
     }
 }
 
 #[test]
-/// If the transmission ends before all params are sent, then the command is
-/// considered recieved and should run with what we have.
-fn it_collects_command_parameters_even_if_transmission_ends() {
-    let ssd1306: &mut SSD1306State = unsafe {
-        let state =
-            std::alloc::alloc(std::alloc::Layout::new::<SSD1306State>()).cast::<SSD1306State>();
-        NonNull::new_unchecked(state).as_mut()
-    };
-    unsafe {
+/// If the transmission ends before all params are sent, the command stays
+/// pending and the next transfer sends the remaining parameters.
+fn it_collects_command_parameters_across_transfers() {
+    let mut ssd1306 = command_mode();
+    {
         ssd1306.i2c_send(0x26); // one of the commands that expect 6 parameters
         assert_eq!(ssd1306.command, 0x26);
-        assert_eq!(ssd1306.parameters.len(), 0);
+        assert_eq!(ssd1306.parameters().len(), 0);
 
         ssd1306.i2c_send(2);
         ssd1306.i2c_send(3);
         ssd1306.i2c_send(4);
         assert_eq!(ssd1306.in_command, true); // we haven't sent all 6 params yet
-                                              // TODO: ssd1306.i2c_event(ENDED);
+        ssd1306.i2c_event(I2C_FINISH);
+        assert_eq!(ssd1306.in_command, true);
+        assert_eq!(ssd1306.parameters(), [2, 3, 4]);
 
+        ssd1306.i2c_event(I2C_START_SEND);
+        ssd1306.i2c_send(0x00); // control byte, D/C# = 0
+        ssd1306.i2c_send(5);
+        ssd1306.i2c_send(6);
+        ssd1306.i2c_send(7);
         assert_eq!(ssd1306.in_command, false);
-        assert_eq!(ssd1306.parameters.len(), 3);
-        assert_eq!(ssd1306.parameters, [2, 3, 4]);
+        assert_eq!(ssd1306.parameters(), [2, 3, 4, 5, 6, 7]);
+    };
+}
+
+#[test]
+fn it_advances_pointers_in_every_addressing_mode() {
+    let mut ssd1306 = command_mode();
+
+    // Horizontal: column first, then page. 0x21/0x22 move the pointers to
+    // the start of the window.
+    for byte in [0x20, 0x00, 0x21, 10, 11, 0x22, 2, 3] {
+        ssd1306.i2c_send(byte);
+    }
+    assert_eq!(ssd1306.column_address_pointer, 10);
+    assert_eq!(ssd1306.page_address_pointer, 2);
+    write_data(&mut ssd1306, &[1, 2, 3, 4, 5]);
+    // The fifth byte wrapped back to the start of the window.
+    assert_eq!(ssd1306.gddram[2 * 128 + 10..2 * 128 + 12], [5, 2]);
+    assert_eq!(ssd1306.gddram[3 * 128 + 10..3 * 128 + 12], [3, 4]);
+    assert_eq!(ssd1306.column_address_pointer, 11);
+    assert_eq!(ssd1306.page_address_pointer, 2);
+
+    // Vertical: page first, then column.
+    for byte in [0x20, 0x01, 0x21, 10, 11, 0x22, 2, 3] {
+        ssd1306.i2c_send(byte);
+    }
+    write_data(&mut ssd1306, &[6, 7, 8, 9]);
+    assert_eq!(ssd1306.gddram[2 * 128 + 10], 6);
+    assert_eq!(ssd1306.gddram[3 * 128 + 10], 7);
+    assert_eq!(ssd1306.gddram[2 * 128 + 11], 8);
+    assert_eq!(ssd1306.gddram[3 * 128 + 11], 9);
+    assert_eq!(ssd1306.column_address_pointer, 10);
+    assert_eq!(ssd1306.page_address_pointer, 2);
+
+    // Page: the column wraps back to the start address, the page never
+    // changes. 0xB5 selects PAGE5, 0x0B and 0x10 column 11.
+    for byte in [0x20, 0x02, 0xb5, 0x0b, 0x10] {
+        ssd1306.i2c_send(byte);
+    }
+    write_data(&mut ssd1306, &[0xaa, 0xbb]);
+    assert_eq!(ssd1306.gddram[5 * 128 + 11], 0xaa);
+    assert_eq!(ssd1306.gddram[5 * 128 + 10], 0xbb);
+    assert_eq!(ssd1306.column_address_pointer, 11);
+    assert_eq!(ssd1306.page_address_pointer, 5);
+}
+
+#[test]
+fn it_sets_the_page_mode_start_address() {
+    let mut ssd1306 = command_mode();
+
+    // Lower nibble, then higher nibble of the column.
+    ssd1306.i2c_send(0x0f);
+    assert_eq!(ssd1306.column_address_pointer, 0x0f);
+    ssd1306.i2c_send(0x17);
+    assert_eq!(ssd1306.column_address_pointer, 127);
+    ssd1306.i2c_send(0x03);
+    assert_eq!(ssd1306.column_address_pointer, 0x73);
+    // X[3] of the higher nibble would be past column 127 and is ignored.
+    ssd1306.i2c_send(0x1f);
+    assert_eq!(ssd1306.column_address_pointer, 0x73);
+
+    for page in 0..8 {
+        ssd1306.i2c_send(0xb0 | page);
+        assert_eq!(ssd1306.page_address_pointer, page);
+    }
+    ssd1306.i2c_send(0xb3);
+    assert_eq!(ssd1306.in_command, false);
+
+    write_data(&mut ssd1306, &[0x5a]);
+    assert_eq!(ssd1306.gddram[3 * 128 + 0x73], 0x5a);
+    assert_eq!(ssd1306.column_address_pointer, 0x74);
+    assert_eq!(ssd1306.page_address_pointer, 3);
+}
+
+#[test]
+fn it_scrolls_the_configured_pages() {
+    let mut ssd1306 = command_mode();
+
+    // Activating without a scroll setup command is ignored.
+    ssd1306.i2c_send(0x2f);
+    assert_eq!(ssd1306.scroll_active, false);
+
+    // Right horizontal scroll of PAGE1 to PAGE2, one step every 2 frames.
+    for byte in [0x26, 0x00, 1, 0b111, 2, 0x00, 0xff] {
+        ssd1306.i2c_send(byte);
+    }
+    assert_eq!(ssd1306.scroll_configured, true);
+    assert_eq!(ssd1306.scroll_left, false);
+    assert_eq!(ssd1306.scroll_start_page, 1);
+    assert_eq!(ssd1306.scroll_end_page, 2);
+    assert_eq!(ssd1306.scroll_interval_frames, 2);
+    assert_eq!(ssd1306.scroll_vertical_step, 0);
+
+    // An end page before the start page is rejected.
+    for byte in [0x27, 0x00, 3, 0b000, 1, 0x00, 0xff] {
+        ssd1306.i2c_send(byte);
+    }
+    assert_eq!(ssd1306.scroll_left, false);
+    assert_eq!(ssd1306.scroll_start_page, 1);
+    assert_eq!(ssd1306.scroll_interval_frames, 2);
+
+    ssd1306.gddram[127] = 0x11;
+    ssd1306.gddram[128 + 127] = 0x22;
+    ssd1306.gddram[2 * 128 + 127] = 0x33;
+
+    // Nothing moves until the scroll is activated.
+    ssd1306.scroll_step();
+    assert_eq!(ssd1306.gddram[128 + 127], 0x22);
+
+    ssd1306.i2c_send(0x2f);
+    assert_eq!(ssd1306.scroll_active, true);
+    ssd1306.scroll_step();
+    assert_eq!(ssd1306.gddram[127], 0x11);
+    assert_eq!(ssd1306.gddram[128], 0x22);
+    assert_eq!(ssd1306.gddram[2 * 128], 0x33);
+
+    ssd1306.i2c_send(0x2e);
+    assert_eq!(ssd1306.scroll_active, false);
+    ssd1306.scroll_step();
+    assert_eq!(ssd1306.gddram[128], 0x22);
+}
+
+#[test]
+fn it_scrolls_the_vertical_scroll_area() {
+    let mut ssd1306 = command_mode();
+    ssd1306.i2c_send(0xaf); // display on
+
+    // The fixed and scroll areas must fit on the 64 COM lines.
+    for byte in [0xa3, 10, 60] {
+        ssd1306.i2c_send(byte);
+    }
+    assert_eq!(ssd1306.vertical_scroll_fixed_rows, 0);
+    assert_eq!(ssd1306.vertical_scroll_rows, 64);
+
+    // Row 0 is fixed, rows 1 to 63 scroll.
+    for byte in [0xa3, 1, 63] {
+        ssd1306.i2c_send(byte);
+    }
+    assert_eq!(ssd1306.vertical_scroll_fixed_rows, 1);
+    assert_eq!(ssd1306.vertical_scroll_rows, 63);
+
+    // Light GDDRAM rows 0 and 1 of column 0.
+    ssd1306.gddram[0] = 0b11;
+
+    // Vertical and right horizontal scroll of PAGE7, one row per step.
+    for byte in [0x29, 0x00, 7, 0b111, 7, 1] {
+        ssd1306.i2c_send(byte);
+    }
+    assert_eq!(ssd1306.scroll_vertical_step, 1);
+    ssd1306.i2c_send(0x2f);
+    ssd1306.scroll_step();
+    assert_eq!(ssd1306.scroll_vertical_offset, 1);
+
+    assert!(ssd1306.pixel_lit(0, 0, 64));
+    // The scroll area moved up by one row, GDDRAM row 1 wrapped around to
+    // the bottom of the area.
+    assert!(!ssd1306.pixel_lit(0, 1, 64));
+    assert!(ssd1306.pixel_lit(0, 63, 64));
+
+    // Deactivating the scroll puts the rows back.
+    ssd1306.i2c_send(0x2e);
+    assert_eq!(ssd1306.scroll_vertical_offset, 0);
+    assert!(ssd1306.pixel_lit(0, 1, 64));
+    assert!(!ssd1306.pixel_lit(0, 63, 64));
+}
+
+#[test]
+fn it_applies_the_display_state_to_pixels() {
+    let mut ssd1306 = command_mode();
+    // Light GDDRAM row 1 of column 0.
+    ssd1306.gddram[0] = 0b10;
+
+    // Nothing is shown while the display is off.
+    assert!(!ssd1306.pixel_lit(0, 1, 64));
+    ssd1306.i2c_send(0xaf);
+    assert!(ssd1306.pixel_lit(0, 1, 64));
+    assert!(!ssd1306.pixel_lit(0, 0, 64));
+
+    // Inverse display.
+    ssd1306.i2c_send(0xa7);
+    assert!(!ssd1306.pixel_lit(0, 1, 64));
+    assert!(ssd1306.pixel_lit(0, 0, 64));
+    ssd1306.i2c_send(0xa6);
+
+    // Entire display on ignores the GDDRAM content.
+    ssd1306.i2c_send(0xa5);
+    assert!(ssd1306.pixel_lit(5, 40, 64));
+    ssd1306.i2c_send(0xa4);
+    assert!(!ssd1306.pixel_lit(5, 40, 64));
+
+    // Segment re-map drives column 0 on the last segment.
+    ssd1306.i2c_send(0xa1);
+    assert!(ssd1306.pixel_lit(127, 1, 64));
+    assert!(!ssd1306.pixel_lit(0, 1, 64));
+    ssd1306.i2c_send(0xa0);
+
+    // Display start line 1 shows GDDRAM row 1 on the first row.
+    ssd1306.i2c_send(0x41);
+    assert!(ssd1306.pixel_lit(0, 0, 64));
+    assert!(!ssd1306.pixel_lit(0, 1, 64));
+    ssd1306.i2c_send(0x40);
+
+    // So does a display offset of 1, by shifting the COM lines.
+    ssd1306.i2c_send(0xd3);
+    ssd1306.i2c_send(1);
+    assert_eq!(ssd1306.display_offset, 1);
+    assert!(ssd1306.pixel_lit(0, 0, 64));
+    assert!(!ssd1306.pixel_lit(0, 1, 64));
+}
+
+#[test]
+fn it_restores_the_reset_state() {
+    let mut ssd1306 = command_mode();
+    for byte in [0x20, 0x00, 0x81, 0x10, 0xa7, 0xaf, 0xa8, 0x1f, 0x26] {
+        ssd1306.i2c_send(byte);
+    }
+    assert_eq!(ssd1306.in_command, true);
+
+    ssd1306.reset();
+    assert!(matches!(
+        ssd1306.memory_addressing_mode,
+        MemoryAddressingMode::Page
+    ));
+    assert_eq!(ssd1306.contrast, 0x7f);
+    assert_eq!(ssd1306.multiplex_ratio, 63);
+    assert_eq!(ssd1306.column_end_address, 127);
+    assert_eq!(ssd1306.page_end_address, 7);
+    assert_eq!(ssd1306.display_enabled, false);
+    assert_eq!(ssd1306.display_inverted, false);
+    assert_eq!(ssd1306.in_command, false);
+    assert_eq!(ssd1306.parameters().len(), 0);
+    assert_eq!(ssd1306.recieved_dc, false);
+}
+
+#[test]
+fn it_maps_com_lines_for_every_panel_height() {
+    let mut ssd1306 = command_mode();
+    ssd1306.i2c_send(0xaf); // display on
+
+    // Light GDDRAM rows 5 and 20 of column 3.
+    ssd1306.gddram[3] = 1 << 5;
+    ssd1306.gddram[2 * 128 + 3] = 1 << 4;
+
+    // 128x64 panels are wired for the alternative COM pins configuration.
+    assert!(ssd1306.pixel_lit(3, 5, 64));
+    assert!(ssd1306.pixel_lit(3, 20, 64));
+    assert!(!ssd1306.pixel_lit(3, 10, 64));
+
+    // 128x32 and 96x16 panels need the sequential configuration.
+    for (mux, height) in [(0x1f, 32), (0x0f, 16)] {
+        for byte in [0xda, 0x02, 0xa8, mux] {
+            ssd1306.i2c_send(byte);
+        }
+        assert!(ssd1306.pixel_lit(3, 5, height));
+        assert!(!ssd1306.pixel_lit(3, 10, height));
+        assert_eq!(ssd1306.pixel_lit(3, 20, height), height == 32);
+    }
+
+    // Keeping the alternative configuration interleaves the rows.
+    ssd1306.i2c_send(0xda);
+    ssd1306.i2c_send(0x12);
+    ssd1306.i2c_send(0xa8);
+    ssd1306.i2c_send(0x1f);
+    assert!(ssd1306.pixel_lit(3, 10, 32));
+    assert!(!ssd1306.pixel_lit(3, 5, 32));
+}
+
+#[test]
+fn it_restores_the_migrated_state() {
+    let mut ssd1306 = command_mode();
+    for byte in [0x20, 0x01, 0x26, 0x00, 0x01] {
+        ssd1306.i2c_send(byte);
+    }
+    ssd1306.pre_save();
+
+    // Only the migrated fields are kept by the destination.
+    let mut restored = SSD1306Registers {
+        memory_addressing_mode: MemoryAddressingMode::Page,
+        ..ssd1306
     };
+    assert_eq!(restored.post_load(), Ok(()));
+    assert!(matches!(
+        restored.memory_addressing_mode,
+        MemoryAddressingMode::Vertical
+    ));
+    assert_eq!(restored.in_command, true);
+    assert_eq!(restored.parameters(), [0x00, 0x01]);
+
+    // A page pointer past PAGE7 would write outside of the GDDRAM.
+    restored.page_address_pointer = 8;
+    assert_eq!(restored.post_load(), Err(()));
+
+    // The next parameter of a complete pending command would overflow the
+    // parameter buffer.
+    restored.page_address_pointer = 0;
+    restored.parameter_count = restored.params_number;
+    assert_eq!(restored.post_load(), Err(()));
+}
+
+#[test]
+fn it_decodes_interleaved_control_bytes() {
+    let mut ssd1306 = SSD1306Registers::default();
+    ssd1306.i2c_event(I2C_START_SEND);
+    // Co = 1: a command, then a parameter, each behind its own control byte.
+    for byte in [0x80, 0xaf, 0x80, 0x20, 0x80, 0x00] {
+        ssd1306.i2c_send(byte);
+    }
+    assert_eq!(ssd1306.display_enabled, true);
+    assert!(matches!(
+        ssd1306.memory_addressing_mode,
+        MemoryAddressingMode::Horizontal
+    ));
+
+    // Co = 1 with D/C# = 1 writes a single data byte.
+    ssd1306.i2c_send(0xc0);
+    ssd1306.i2c_send(0x11);
+    ssd1306.i2c_send(0x80);
+    ssd1306.i2c_send(0xa7); // inverse display, not GDDRAM data
+    assert_eq!(ssd1306.display_inverted, true);
+
+    // Co = 0: every following byte is data until the end of the transfer.
+    for byte in [0x40, 0x22, 0x80, 0x33] {
+        ssd1306.i2c_send(byte);
+    }
+    assert_eq!(ssd1306.gddram[..4], [0x11, 0x22, 0x80, 0x33]);
+    assert_eq!(ssd1306.column_address_pointer, 4);
+}
+
+#[test]
+fn it_reads_the_status_register_without_ending_the_command() {
+    let mut ssd1306 = command_mode();
+    assert_eq!(ssd1306.status(), 0x40); // display OFF after RESET
+
+    ssd1306.i2c_send(0xaf); // display on
+    ssd1306.i2c_send(0x81); // contrast, expects a parameter
+    ssd1306.i2c_event(I2C_START_RECV); // repeated START to read the status
+    assert_eq!(ssd1306.status(), 0x00);
+    ssd1306.i2c_event(I2C_FINISH);
+    assert_eq!(ssd1306.in_command, true);
+
+    ssd1306.i2c_event(I2C_START_SEND);
+    ssd1306.i2c_send(0x00); // control byte, D/C# = 0
+    ssd1306.i2c_send(0x10);
+    assert_eq!(ssd1306.in_command, false);
+    assert_eq!(ssd1306.contrast, 0x10);
+}
+
+#[test]
+fn it_dims_pixels_with_contrast_and_precharge() {
+    let mut ssd1306 = command_mode();
+    let reset = ssd1306.pixel_intensity();
+
+    ssd1306.i2c_send(0x81);
+    ssd1306.i2c_send(0xff);
+    assert_eq!(ssd1306.pixel_intensity(), 0xff);
+
+    ssd1306.i2c_send(0x81);
+    ssd1306.i2c_send(0x00);
+    let dimmed = ssd1306.pixel_intensity();
+    assert!(dimmed < reset);
+
+    // Phase 2 of 1 DCLK, shorter than the RESET value.
+    ssd1306.i2c_send(0xd9);
+    ssd1306.i2c_send(0x11);
+    assert!(ssd1306.pixel_intensity() < dimmed);
+
+    // Invalid periods are ignored.
+    ssd1306.i2c_send(0xd9);
+    ssd1306.i2c_send(0xf0);
+    assert_eq!(ssd1306.precharge_period, 0x11);
+}
+
+#[test]
+fn it_keeps_an_internal_vcc_panel_dark_without_the_charge_pump() {
+    let mut ssd1306 = command_mode();
+    ssd1306.internal_vcc = true;
+    ssd1306.gddram[0] = 1;
+    ssd1306.i2c_send(0xaf); // display on
+    assert!(!ssd1306.pixel_lit(0, 0, 64));
+
+    ssd1306.i2c_send(0x8d);
+    ssd1306.i2c_send(0x14); // enable the charge pump
+    assert!(ssd1306.pixel_lit(0, 0, 64));
+
+    // The module wiring survives RESET, the charge pump setting does not.
+    ssd1306.reset();
+    assert_eq!(ssd1306.internal_vcc, true);
+    assert_eq!(ssd1306.charge_pump_enabled, false);
+}
+
+#[test]
+fn it_ramps_the_contrast_when_fading_and_blinking() {
+    let mut ssd1306 = command_mode();
+    let full = ssd1306.pixel_intensity();
+
+    // Fade out, 8 frames per step.
+    ssd1306.i2c_send(0x23);
+    ssd1306.i2c_send(0x20);
+    assert_eq!(ssd1306.in_command, false);
+    assert!(ssd1306.fade_active());
+    let fast = ssd1306.fade_step_period_ns();
+    ssd1306.fade_step();
+    assert!(ssd1306.pixel_intensity() < full);
+    while ssd1306.fade_active() {
+        ssd1306.fade_step();
+    }
+    assert_eq!(ssd1306.pixel_intensity(), 0);
+
+    // Blinking, 128 frames per step, comes back to full brightness.
+    ssd1306.i2c_send(0x23);
+    ssd1306.i2c_send(0x3f);
+    assert_eq!(ssd1306.fade_step_period_ns(), 16 * fast);
+    for _ in 0..16 {
+        ssd1306.fade_step();
+    }
+    assert_eq!(ssd1306.pixel_intensity(), 0);
+    for _ in 0..16 {
+        ssd1306.fade_step();
+    }
+    assert_eq!(ssd1306.pixel_intensity(), full);
+    assert!(ssd1306.fade_active());
+
+    // The zoom in parameter is not taken for a command.
+    ssd1306.i2c_send(0xd6);
+    ssd1306.i2c_send(0x01);
+    assert_eq!(ssd1306.zoom_in, true);
+    assert_eq!(ssd1306.in_command, false);
+    assert_eq!(ssd1306.display_enabled, false);
 }
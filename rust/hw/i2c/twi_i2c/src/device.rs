@@ -1,25 +1,39 @@
 use core::ptr::NonNull;
 use std::{
-    ffi::{c_uint, c_void, CStr},
+    ffi::CStr,
+    pin::Pin,
     ptr::{addr_of, addr_of_mut},
 };
 
 use qemu_api::{
     bindings::{
-        hwaddr, i2c_end_transfer, i2c_init_bus, i2c_send, memory_region_init_io, qemu_irq,
-        qemu_set_irq, sysbus_init_irq, sysbus_init_mmio, I2CBus, MemoryRegion,
+        error_fatal, i2c_ack, i2c_end_transfer, i2c_init_bus, i2c_nack, i2c_recv, i2c_send,
+        i2c_slave_new, i2c_slave_realize_and_unref, i2c_slave_set_address, timer_expire_time_ns,
+        Error, I2CBus, I2C_FINISH, I2C_NACK, I2C_START_RECV, I2C_START_SEND, I2C_START_SEND_ASYNC,
     },
     c_str,
-    cell::BqlRefCell,
+    cell::{BqlCell, BqlRefCell},
+    irq::InterruptSource,
+    memory::{hwaddr, MemoryRegion, MemoryRegionOps, MemoryRegionOpsBuilder},
     qdev::{DeviceImpl, DeviceState, Property, ResetType, ResettablePhasesImpl},
     qom::{IsA, Object, ObjectImpl, ObjectType, ParentField},
     qom_isa,
-    sysbus::{SysBusDevice, SysBusDeviceImpl},
+    sysbus::{SysBusDevice, SysBusDeviceImpl, SysBusDeviceMethods},
+    timer::{Timer, CLOCK_VIRTUAL},
     vmstate::VMStateDescription,
 };
 use qemu_api_macros::Object;
 
-use crate::{memory_ops::TWI_I2C_OPS, registers};
+use crate::{
+    registers::{self, SlaveMode},
+    slave::TWISlaveState,
+};
+
+/// SCL periods taken by a START, repeated START or STOP condition.
+const CONDITION_SCL_PERIODS: u64 = 1;
+
+/// SCL periods taken by a byte and its acknowledge bit.
+const BYTE_SCL_PERIODS: u64 = 9;
 
 #[derive(Object, qemu_api_macros::offsets)]
 #[repr(C)]
@@ -28,10 +42,21 @@ pub struct TWIState {
     pub iomem: MemoryRegion,
     pub someprop: char,
     pub bus: *mut I2CBus,
-    pub irq: qemu_irq,
+    /// Level interrupt, raised while TWINT and TWIE are set.
+    pub irq: InterruptSource,
 
     pub registers: BqlRefCell<registers::TWIRegisters>,
-    pub enabled: bool,
+    pub enabled: BqlCell<bool>,
+    /// Acknowledge SLA+R/W even if no slave has the address.
+    pub fake_ack: bool,
+    /// The I2C slave at the TWAR address, created on realize.
+    pub slave: BqlCell<*mut TWISlaveState>,
+    /// Frequency of the CPU clock, which drives the bit rate generator.
+    pub cpu_freq_hz: u64,
+    /// Fires when the current bus operation is complete.
+    pub timer: Timer,
+    /// Expiry time of `timer` as migrated, `u64::MAX` if it is not pending.
+    pub migration_deadline_ns: BqlCell<u64>,
 }
 
 trait TWIImpl: SysBusDeviceImpl + IsA<TWIState> {}
@@ -47,7 +72,7 @@ impl ObjectImpl for TWIState {
     type ParentType = SysBusDevice;
 
     const INSTANCE_INIT: Option<unsafe fn(&mut Self)> = Some(Self::init);
-    const INSTANCE_POST_INIT: Option<fn(&Self)> = None;
+    const INSTANCE_POST_INIT: Option<fn(&Self)> = Some(Self::post_init);
     const CLASS_INIT: fn(&mut Self::Class) = Self::Class::class_init::<Self>;
 }
 
@@ -74,95 +99,118 @@ impl TWIState {
     /// `TWI_I2CState` type. It must not be called more than once on the same
     /// location/instance. All its fields are expected to hold unitialized
     /// values with the sole exception of `parent_obj`.
-    pub fn init(&mut self) {
+    unsafe fn init(&mut self) {
         println!("init twi");
+        static TWI_I2C_OPS: MemoryRegionOps<TWIState> = MemoryRegionOpsBuilder::<TWIState>::new()
+            .read(&TWIState::read)
+            .write(&TWIState::write)
+            .native_endian()
+            .valid_sizes(1, 1)
+            .impl_sizes(1, 1)
+            .build();
+
+        // SAFETY:
+        //
+        // self and self.iomem are guaranteed to be valid at this point since callers
+        // must make sure the `self` reference is valid.
+        MemoryRegion::init_io(
+            unsafe { &mut *addr_of_mut!(self.iomem) },
+            addr_of_mut!(*self),
+            &TWI_I2C_OPS,
+            "TWI_I2C",
+            0x6,
+        );
 
         let device = addr_of_mut!(*self).cast::<qemu_api::bindings::DeviceState>();
-        let sbd = addr_of_mut!(*self).cast::<qemu_api::bindings::SysBusDevice>();
         unsafe {
-            sysbus_init_mmio(sbd, addr_of_mut!(self.iomem));
-            sysbus_init_irq(sbd, &mut self.irq);
+            // Peripherals are attached to this bus by the board or with -device.
             self.bus = i2c_init_bus(device, c_str!("i2c-bus").as_ptr());
-            qemu_api::bindings::i2c_slave_create_simple(
-                self.bus,
-                c_str!("ssd1306").as_ptr(),
-                0x3d_u8,
-            );
         }
+
+        // SAFETY: the timer is only used as part of TWIState, which is always
+        // pinned, and outlives it.
+        let timer = unsafe { Pin::new_unchecked(&mut *addr_of_mut!(self.timer)) };
+        timer.init_full(
+            None,
+            CLOCK_VIRTUAL,
+            Timer::NS,
+            0,
+            Self::bus_operation_done,
+            unsafe { &*addr_of!(*self) },
+        );
+    }
+
+    fn post_init(&self) {
+        self.init_mmio(&self.iomem);
+        self.init_irq(&self.irq);
     }
 
     pub fn realize(&self) {
         println!("realize twi");
         unsafe {
-            memory_region_init_io(
-                addr_of!(self.iomem) as *mut _,
-                addr_of!(*self).cast::<Object>() as *mut _,
-                &TWI_I2C_OPS,
-                addr_of!(*self).cast::<c_void>() as *mut _,
-                Self::TYPE_INFO.name,
-                0x6,
+            // Other masters on the bus address the TWI through this slave.
+            let slave =
+                i2c_slave_new(crate::TYPE_TWI_I2C_SLAVE.as_ptr(), 0).cast::<TWISlaveState>();
+            (*slave).twi = self;
+            i2c_slave_realize_and_unref(
+                slave.cast(),
+                self.bus,
+                addr_of!(error_fatal) as *mut *mut Error,
             );
+            self.slave.set(slave);
         }
+        self.update_slave_address();
     }
 
     /// Reset the TWI controller.
     pub fn reset_hold(&self, _type: ResetType) {
         println!("reset");
+        // Release the bus before forgetting about the transfer.
+        self.abort();
         self.registers.borrow_mut().reset();
-        //unsafe {
-        //    s.in_transaction = false;
-        //}
+        self.enabled.set(false);
+        self.update_irq();
+        self.update_slave_address();
     }
 
-    pub fn read(&mut self, offset: hwaddr, _size: c_uint) -> u64 {
+    fn read(&self, offset: hwaddr, _size: u32) -> u64 {
         //println!("read address: {}: size: {}", offset, size);
-        match offset {
-            0 => {
-                //println!("reading: reg 0");
-            }
-            1 => {
-                let x: u8 = self.registers.borrow().twsr.into();
-                return u64::from(x);
-            }
-            2 => {
-                //println!("reading: reg 1");
-            }
-            3 => {
-                //println!("reading: reg 3");
-            }
-            4 => {
-                //println!("reading: reg 4");
-                let x: u8 = self.registers.borrow().twcr.into();
-                return u64::from(x);
-            }
-            5 => {
-                //println!("reading: reg 5");
-            }
+        let registers = self.registers.borrow();
+        let value: u8 = match offset {
+            0 => registers.twbr.into(),
+            1 => registers.twsr.into(),
+            2 => registers.twar.into(),
+            3 => registers.twdr.into(),
+            4 => registers.twcr.into(),
+            5 => registers.twamr.into(),
             _ => {
                 //eprintln!("reading: bad offset");
+                0xFF
             }
-        }
-        0xFF
+        };
+        u64::from(value)
     }
 
-    pub fn write(&mut self, address: hwaddr, data: u8) {
-        //println!("write address: {}: data: {}", address, data);
+    fn write(&self, offset: hwaddr, value: u64, _size: u32) {
+        //println!("write address: {}: data: {}", offset, value);
+        let data = value as u8;
         let mut registers = self.registers.borrow_mut();
-        match address {
+        match offset {
             0 => {
                 // set the bit rate
-                println!("setting TWI bit rate");
-                let _r = registers::TWBR::from(data);
+                registers.twbr = registers::TWBR::from(data);
             }
             1 => {
-                // TODO: handle the first two bits.
-                let _r = registers::TWSR::from(data);
+                // Only the prescaler bits are writable.
+                let r = registers::TWSR::from(data);
+                registers.twsr.set_twps0(r.twps0());
+                registers.twsr.set_twps1(r.twps1());
             }
             2 => {
                 // set address
                 registers.twar = registers::TWAR::from(data);
-                // TODO: handle the LSB
-                println!("slave address set: {}", u8::from(registers.twar) >> 1);
+                drop(registers);
+                self.update_slave_address();
             }
             3 => {
                 // set data
@@ -181,11 +229,30 @@ pub fn write(&mut self, address: hwaddr, data: u8) {
             4 => {
                 let r = registers::TWCR::from(data);
                 //println!("{:#?}", r);
-                // TODO: if this bit is reset, terminate all on going trasmissions
-                self.enabled = r.twen();
-                registers.twcr.set_twint(r.twint());
+                // An asynchronous master is held in Slave Receiver mode until
+                // the firmware clears TWINT.
+                let release_master = r.twint()
+                    && registers.twcr.twint()
+                    && matches!(
+                        registers.slave_mode,
+                        SlaveMode::Receiver | SlaveMode::GeneralCall
+                    );
+                if self.enabled.get() && !r.twen() {
+                    // Switching the TWI off terminates all transmissions.
+                    drop(registers);
+                    self.abort();
+                    registers = self.registers.borrow_mut();
+                }
+                self.enabled.set(r.twen());
+                registers.twcr.set_twen(r.twen());
+                if r.twint() {
+                    // TWINT is cleared by writing a logic one to it.
+                    registers.twcr.set_twint(false);
+                }
 
-                if r.twsta() {
+                registers.twcr.set_twie(r.twie());
+                registers.twcr.set_twea(r.twea());
+                if r.twsta() && r.twen() {
                     registers.twcr.set_twsta(true);
                 }
 
@@ -196,20 +263,53 @@ pub fn write(&mut self, address: hwaddr, data: u8) {
                 } else if r.twint() && r.twen() {
                     // TODO: The global interrupt must be enabled SREG (I)
 
+                    // In Master Receiver mode, clearing TWINT receives the next byte,
+                    // except right after SLA+R where it only acknowledges the address.
+                    let receive = registers.in_transaction
+                        && registers.master_receiver
+                        && !registers.sla_read_pending
+                        && !r.twsta();
+                    registers.sla_read_pending = false;
+
                     // This is a new transaction
-                    if !registers.in_transaction {
-                        // TODO: don't touch the first two bits when setting the status
+                    if !registers.in_transaction && r.twsta() {
                         registers.in_transaction = true;
-                        registers.twsr = registers::TW_START.into();
+                        registers.set_status(registers::TW_START);
                         registers.twcr.set_twen(true);
+                    } else if registers.in_transaction && r.twsta() {
+                        // Repeated START, the next SLA+R/W is sent without a STOP.
+                        registers.set_status(registers::TW_REP_START);
+                        registers.master_receiver = false;
                     }
 
-                    unsafe {
-                        // TODO: use the new InterruptSource API
-                        qemu_set_irq(self.irq, 1);
-                    };
-                    registers.twcr.set_twint(true); // Fake TWI
+                    // Writing one clears TWINT. In master mode it is set again
+                    // once the operation is done on the bus, in slave mode by
+                    // the next bus event.
+                    registers.twcr.set_twint(false);
+                    let master = registers.in_transaction;
+
+                    drop(registers);
+                    if receive {
+                        self.receive_data(r.twea());
+                    }
+                    if master {
+                        self.schedule_completion(if r.twsta() {
+                            CONDITION_SCL_PERIODS
+                        } else {
+                            BYTE_SCL_PERIODS
+                        });
+                    }
+                } else {
+                    drop(registers);
                 }
+                self.update_irq();
+                if release_master {
+                    unsafe { i2c_ack(self.bus) };
+                }
+            }
+            5 => {
+                // set the slave address mask, bit 0 is reserved
+                registers.twamr = registers::TWAMR::from(data & 0xfe);
             }
             _ => {
                 // TODO: better error reporting
@@ -218,24 +318,118 @@ pub fn write(&mut self, address: hwaddr, data: u8) {
         }
     }
 
+    pub fn pre_save(&self) {
+        self.registers.borrow_mut().pre_save();
+        // SAFETY: the timer was initialized in `init`.
+        let deadline = unsafe { timer_expire_time_ns(self.timer.as_mut_ptr()) };
+        self.migration_deadline_ns.set(deadline);
+    }
+
+    pub fn post_load(&self, _version_id: u32) -> Result<(), ()> {
+        self.registers.borrow_mut().post_load()?;
+        match self.migration_deadline_ns.get() {
+            u64::MAX => self.timer.delete(),
+            deadline => self.timer.modify(deadline),
+        }
+        self.update_irq();
+        self.update_slave_address();
+        Ok(())
+    }
+
     /// Handle a START condition.
     const fn _start(&self) {}
 
     /// Handle a STOP condition.
     fn stop(&self) {
+        // In slave mode, TWSTO only returns to the unaddressed state.
+        let master = self.registers.borrow().in_transaction;
+        if master {
+            unsafe {
+                i2c_end_transfer(self.bus);
+                //println!("ended transfer");
+            };
+        }
         let mut registers = self.registers.borrow_mut();
-        unsafe {
-            i2c_end_transfer(self.bus);
-            //println!("ended transfer");
-        };
         registers.in_transaction = false;
-        registers.twcr.set_twsto(false); // report that STOP has executed on the bus.
+        registers.master_receiver = false;
+        registers.sla_read_pending = false;
+        registers.master_address = None;
+        registers.slave_mode = SlaveMode::Idle;
         registers.twcr.set_twint(false); // TODO: according to twi_stop
                                          // in the arduino library, confirm
                                          // in
                                          // the data sheet
+        if master {
+            // TWSTO is cleared once the STOP condition is executed on the bus.
+            registers.twcr.set_twsto(true);
+            drop(registers);
+            self.schedule_completion(CONDITION_SCL_PERIODS);
+        } else {
+            registers.twcr.set_twsto(false);
+        }
+    }
+
+    /// Duration of one SCL period, `CPU / (16 + 2 * TWBR * 4^TWPS)`.
+    fn scl_period_ns(&self) -> u64 {
+        if self.cpu_freq_hz == 0 {
+            return 0;
+        }
+        let registers = self.registers.borrow();
+        let twps = u32::from(registers.twsr.twps1()) << 1 | u32::from(registers.twsr.twps0());
+        let cycles = 16 + 2 * u64::from(u8::from(registers.twbr)) * 4_u64.pow(twps);
+        cycles * 1_000_000_000 / self.cpu_freq_hz
+    }
+
+    /// Complete the current bus operation after `periods` SCL periods.
+    fn schedule_completion(&self, periods: u64) {
+        self.timer
+            .modify(CLOCK_VIRTUAL.get_ns() + periods * self.scl_period_ns());
+    }
+
+    /// The bus operation started by the last TWCR write is complete.
+    fn bus_operation_done(&self) {
+        let mut registers = self.registers.borrow_mut();
+        if registers.twcr.twsto() {
+            registers.twcr.set_twsto(false); // report that STOP has executed on the bus.
+            return;
+        }
+        registers.twcr.set_twint(true);
+        drop(registers);
+        self.update_irq();
+    }
+
+    /// Drive the interrupt line from TWINT and TWIE.
+    fn update_irq(&self) {
+        let registers = self.registers.borrow();
+        self.irq
+            .set(registers.twcr.twint() && registers.twcr.twie());
     }
+    /// Terminate any transfer and return to the idle state, when TWEN is
+    /// cleared.
+    fn abort(&self) {
+        self.timer.delete();
+        if self.registers.borrow().in_transaction {
+            unsafe { i2c_end_transfer(self.bus) };
+        }
+
+        let mut registers = self.registers.borrow_mut();
+        registers.in_transaction = false;
+        registers.master_receiver = false;
+        registers.sla_read_pending = false;
+        registers.master_address = None;
+        registers.slave_mode = SlaveMode::Idle;
+        registers.twcr.set_twsta(false);
+        registers.twcr.set_twsto(false);
+        registers.twcr.set_twint(false);
+        drop(registers);
+        self.set_status(registers::TW_NO_INFO);
+    }
+
     fn write_data(&self) {
+        if !self.enabled.get() {
+            // TWDR keeps the value, but nothing is transmitted.
+            return;
+        }
         let mut registers = self.registers.borrow_mut();
         // report that you cannot write when twint is low
         if !registers.twcr.twint() {
@@ -244,51 +438,69 @@ fn write_data(&self) {
         }
 
         if registers.twcr.twsta() {
-            // If the START bit was set, then this is the first data in the transaction
-            // and it contains the sla+w (slave address + w bit)
-            //
-            // TODO: THIS IS NOT ALWAYS TRUE, in repeated start, the address wont be sent
-            // again. (not sure)
+            // If the START bit was set, then this is the first data after a
+            // (repeated) START and it contains the SLA+R/W (slave address + R/W bit)
             registers.twcr.set_twsta(false);
-            let start_read = registers.twdr.twd0(); // write bit
-                                                    // TODO: do i use start_transfer when attempting to read?
+            let start_read = registers.twdr.twd0(); // R/W bit
 
             let address = u8::from(registers.twdr) >> 1;
+            let previous_address = registers.master_address.take();
 
             // Release the BqlRefCell before calling set_status
             drop(registers);
 
-            match i2c_start_transfer(self.bus, address, false) {
+            // A repeated START to the same slave only switches the direction of
+            // the QEMU transfer, a different slave needs a new one.
+            if matches!(previous_address, Some(previous) if previous != address) {
+                unsafe { i2c_end_transfer(self.bus) };
+            }
+
+            match i2c_start_transfer(self.bus, address, start_read) {
                 Ok(()) => {
+                    let mut registers = self.registers.borrow_mut();
+                    registers.master_receiver = start_read;
+                    registers.sla_read_pending = start_read;
+                    registers.master_address = Some(address);
+                    drop(registers);
                     self.set_status(if start_read {
                         registers::TW_MR_SLA_ACK
                     } else {
                         registers::TW_MT_SLA_ACK
                     });
                 }
-                Err(()) => {
-                    eprintln!("start transfer failed");
-                    // TODO: im faking ACK for testing
-                    //
-                    //self.set_status(if start_read {
-                    //    registers::TW_MR_SLA_NACK
-                    //} else {
-                    //    registers::TW_MT_SLA_NACK
-                    //});
+                Err(()) if self.fake_ack => {
+                    // Legacy behaviour, some firmware expects every address to
+                    // be present.
+                    let mut registers = self.registers.borrow_mut();
+                    registers.master_receiver = start_read;
+                    registers.sla_read_pending = start_read;
+                    drop(registers);
                     self.set_status(if start_read {
                         registers::TW_MR_SLA_ACK
                     } else {
                         registers::TW_MT_SLA_ACK
                     });
                 }
+                Err(()) => {
+                    // No slave answered the address.
+                    self.set_status(if start_read {
+                        registers::TW_MR_SLA_NACK
+                    } else {
+                        registers::TW_MT_SLA_NACK
+                    });
+                }
             }
+        } else if registers.master_receiver {
+            // TWDR holds the received data, there is nothing to transmit.
         } else {
             // TODO: refactor to a Result<(), ()>
-            let resp = unsafe { i2c_send(self.bus, registers.twdr.into()) };
+            let data = registers.twdr.into();
 
-            // Release the BqlRefCell before calling set_status
+            // Release the BqlRefCell, the slave may be the TWI itself.
             drop(registers);
 
+            let resp = unsafe { i2c_send(self.bus, data) };
+
             if resp == 0 {
                 self.set_status(registers::TW_MT_DATA_ACK);
             } else {
@@ -297,10 +509,160 @@ fn write_data(&self) {
         }
     }
 
+    /// Receive the next byte from the addressed slave into TWDR.
+    ///
+    /// `ack` is the value of TWEA, the master returns a NACK after the last
+    /// byte it wants to receive.
+    fn receive_data(&self, ack: bool) {
+        let data = unsafe { i2c_recv(self.bus) };
+        self.registers.borrow_mut().twdr = data.into();
+
+        if ack {
+            self.set_status(registers::TW_MR_DATA_ACK);
+        } else {
+            unsafe { i2c_nack(self.bus) };
+            self.set_status(registers::TW_MR_DATA_NACK);
+        }
+    }
+
+    /// Move the slave to the address in TWAR.
+    fn update_slave_address(&self) {
+        let slave = self.slave.get();
+        if !slave.is_null() {
+            let address = u8::from(self.registers.borrow().twar) >> 1;
+            unsafe { i2c_slave_set_address(slave.cast(), address) };
+        }
+    }
+
+    /// Whether the 7-bit `address` selects the TWI slave, the bits set in
+    /// TWAMR are ignored in the comparison with TWAR.
+    pub fn slave_address_matches(&self, address: u8) -> bool {
+        let registers = self.registers.borrow();
+        let twa = u8::from(registers.twar) >> 1;
+        let mask = u8::from(registers.twamr) >> 1;
+        (address ^ twa) & !mask == 0
+    }
+
+    /// A master started or ended a transfer with the TWI slave.
+    ///
+    /// Returns non-zero to NACK the address.
+    pub fn slave_event(&self, event: u32, general_call: bool) -> i32 {
+        let mut registers = self.registers.borrow_mut();
+        let status = match event {
+            I2C_START_SEND | I2C_START_SEND_ASYNC | I2C_START_RECV => {
+                // With TWEA cleared the TWI is virtually disconnected from
+                // the bus.
+                if !self.enabled.get()
+                    || !registers.twcr.twea()
+                    || (general_call && (!registers.twar.twgce() || event == I2C_START_RECV))
+                {
+                    registers.slave_mode = SlaveMode::Idle;
+                    return 1;
+                }
+                let (mode, status) = if event == I2C_START_RECV {
+                    (SlaveMode::Transmitter, registers::TW_ST_SLA_ACK)
+                } else if general_call {
+                    (SlaveMode::GeneralCall, registers::TW_SR_GCALL_ACK)
+                } else {
+                    (SlaveMode::Receiver, registers::TW_SR_SLA_ACK)
+                };
+                registers.slave_mode = mode;
+                status
+            }
+            I2C_NACK if registers.slave_mode == SlaveMode::Transmitter => {
+                registers::TW_ST_DATA_NACK
+            }
+            I2C_FINISH => {
+                let mode = registers.slave_mode;
+                registers.slave_mode = SlaveMode::Idle;
+                match mode {
+                    SlaveMode::Receiver | SlaveMode::GeneralCall => registers::TW_SR_STOP,
+                    // The Slave Transmitter does not report the STOP.
+                    SlaveMode::Transmitter | SlaveMode::Idle => return 0,
+                }
+            }
+            _ => return 0,
+        };
+        drop(registers);
+
+        self.slave_interrupt(status);
+        0
+    }
+
+    /// A master wrote `data` to the TWI slave.
+    ///
+    /// The QEMU I2C master can't be stalled until the firmware clears TWINT,
+    /// so bytes that arrive while TWINT is set are NACKed and dropped rather
+    /// than overwriting TWDR and TWSR.
+    ///
+    /// Returns non-zero to NACK the byte.
+    pub fn slave_send(&self, data: u8) -> i32 {
+        if self.registers.borrow().twcr.twint() {
+            return 1;
+        }
+        i32::from(!self.slave_receive_data(data))
+    }
+
+    /// An asynchronous master wrote `data` to the TWI slave.
+    ///
+    /// The master waits for the TWCR write that clears TWINT, see `write`.
+    pub fn slave_send_async(&self, data: u8) {
+        self.slave_receive_data(data);
+    }
+
+    /// Store `data` in TWDR and report it to the firmware.
+    ///
+    /// Returns whether the byte is acknowledged.
+    fn slave_receive_data(&self, data: u8) -> bool {
+        let mut registers = self.registers.borrow_mut();
+        registers.twdr = data.into();
+        let ack = registers.twcr.twea();
+        let general_call = registers.slave_mode == SlaveMode::GeneralCall;
+        drop(registers);
+
+        self.slave_interrupt(match (general_call, ack) {
+            (false, true) => registers::TW_SR_DATA_ACK,
+            (false, false) => registers::TW_SR_DATA_NACK,
+            (true, true) => registers::TW_SR_GCALL_DATA_ACK,
+            (true, false) => registers::TW_SR_GCALL_DATA_NACK,
+        });
+        ack
+    }
+
+    /// A master reads a byte from the TWI slave.
+    ///
+    /// The firmware loads TWDR and clears TWINT to transmit the next byte.
+    /// The QEMU I2C master can't be stalled until then, if TWINT is still set
+    /// the master reads 0xFF from the released bus and the pending interrupt
+    /// is kept.
+    pub fn slave_recv(&self) -> u8 {
+        let registers = self.registers.borrow();
+        if registers.twcr.twint() {
+            return 0xff;
+        }
+        let data = u8::from(registers.twdr);
+        // TWEA cleared means this is the last byte the firmware transmits.
+        let last = !registers.twcr.twea();
+        drop(registers);
+
+        self.slave_interrupt(if last {
+            registers::TW_ST_LAST_DATA
+        } else {
+            registers::TW_ST_DATA_ACK
+        });
+        data
+    }
+
+    /// Report a slave mode `status` to the firmware.
+    fn slave_interrupt(&self, status: u8) {
+        self.set_status(status);
+        self.registers.borrow_mut().twcr.set_twint(true);
+        self.update_irq();
+    }
+
     /// Set the status bits in TWSR.
     fn set_status(&self, status: u8) {
-        // TODO: only modify the last 5 bits.
-        self.registers.borrow_mut().twsr = status.into();
+        self.registers.borrow_mut().set_status(status);
     }
 }
 
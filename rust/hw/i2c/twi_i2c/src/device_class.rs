@@ -1,6 +1,14 @@
-use qemu_api::{bindings::*, c_str, vmstate_fields, vmstate_unused, zeroable::Zeroable};
+use std::{
+    os::raw::{c_int, c_void},
+    ptr::NonNull,
+};
+
+use qemu_api::{
+    bindings::*, c_str, cell::BqlRefCell, vmstate_fields, vmstate_i2c_slave, vmstate_of,
+    vmstate_struct, zeroable::Zeroable,
+};
 
-use crate::device::TWIState;
+use crate::{device::TWIState, registers::TWIRegisters, slave::TWISlaveState};
 
 // TODO: Find a way to not define properties
 qemu_api::declare_properties! {
@@ -12,16 +20,81 @@
         unsafe { &qdev_prop_chr },
         CharBackend
     ),
+    qemu_api::define_property!(
+        c_str!("fake-ack"),
+        TWIState,
+        fake_ack,
+        unsafe { &qdev_prop_bool },
+        bool,
+        default = false
+    ),
+    qemu_api::define_property!(
+        c_str!("cpu-frequency-hz"),
+        TWIState,
+        cpu_freq_hz,
+        unsafe { &qdev_prop_uint64 },
+        u64,
+        // The usual 16 MHz crystal of ATmega328P and ATmega2560 boards.
+        default = 16_000_000
+    ),
+}
+
+extern "C" fn twi_i2c_pre_save(opaque: *mut c_void) -> c_int {
+    let state = NonNull::new(opaque).unwrap().cast::<TWIState>();
+    unsafe { state.as_ref().pre_save() };
+    0
+}
+
+extern "C" fn twi_i2c_post_load(opaque: *mut c_void, version_id: c_int) -> c_int {
+    let state = NonNull::new(opaque).unwrap().cast::<TWIState>();
+    let result = unsafe { state.as_ref().post_load(version_id as u32) };
+    if result.is_err() {
+        -1
+    } else {
+        0
+    }
 }
 
+static VMSTATE_TWI_I2C_REGS: VMStateDescription = VMStateDescription {
+    name: c_str!("twi_i2c/regs").as_ptr(),
+    version_id: 1,
+    minimum_version_id: 1,
+    fields: vmstate_fields! {
+        vmstate_of!(TWIRegisters, twbr),
+        vmstate_of!(TWIRegisters, twsr),
+        vmstate_of!(TWIRegisters, twar),
+        vmstate_of!(TWIRegisters, twdr),
+        vmstate_of!(TWIRegisters, twcr),
+        vmstate_of!(TWIRegisters, twamr),
+        vmstate_of!(TWIRegisters, in_transaction),
+        vmstate_of!(TWIRegisters, master_receiver),
+        vmstate_of!(TWIRegisters, sla_read_pending),
+        vmstate_of!(TWIRegisters, migration_master_address),
+        vmstate_of!(TWIRegisters, migration_slave_mode),
+    },
+    ..Zeroable::ZERO
+};
+
 pub static VMSTATE_TWI_I2C: VMStateDescription = VMStateDescription {
     name: c_str!("twi_i2c").as_ptr(),
+    version_id: 2,
+    minimum_version_id: 2,
+    pre_save: Some(twi_i2c_pre_save),
+    post_load: Some(twi_i2c_post_load),
+    fields: vmstate_fields! {
+        vmstate_struct!(TWIState, registers, &VMSTATE_TWI_I2C_REGS, BqlRefCell<TWIRegisters>),
+        vmstate_of!(TWIState, enabled),
+        vmstate_of!(TWIState, migration_deadline_ns),
+    },
+    ..Zeroable::ZERO
+};
+
+pub static VMSTATE_TWI_I2C_SLAVE: VMStateDescription = VMStateDescription {
+    name: c_str!("twi_i2c-slave").as_ptr(),
     version_id: 1,
     minimum_version_id: 1,
-    post_load: None,
     fields: vmstate_fields! {
-        vmstate_unused!(core::mem::size_of::<u32>()),
-        //vmstate_uint32!(flags, TWI_I2CState),
+        vmstate_i2c_slave!(parent_obj, TWISlaveState),
     },
     ..Zeroable::ZERO
 };
@@ -1,8 +1,9 @@
 pub mod device;
 pub mod device_class;
-pub mod memory_ops;
 pub mod registers;
+pub mod slave;
 
 use qemu_api::c_str;
 
 pub const TYPE_TWI_I2C: &::std::ffi::CStr = c_str!("TWI_I2C");
+pub const TYPE_TWI_I2C_SLAVE: &::std::ffi::CStr = c_str!("TWI_I2C-slave");
@@ -2,6 +2,7 @@
 extern crate bilge_impl;
 
 use bilge::prelude::*;
+use qemu_api::impl_vmstate_bitsized;
 
 /// ATmega640/1280/1281/2560/2561 Datasheet
 ///
@@ -14,15 +15,65 @@ pub enum RegisterOffset {
     TWAR = 0x2,
     TWDR = 0x3,
     TWCR = 0x4,
+    TWAMR = 0x5,
 }
 
+/// How the TWI was addressed by another master.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u8)]
+pub enum SlaveMode {
+    /// Not addressed.
+    Idle,
+    /// Slave Receiver, addressed with SLA+W.
+    Receiver,
+    /// Slave Receiver, addressed with the general call address.
+    GeneralCall,
+    /// Slave Transmitter, addressed with SLA+R.
+    Transmitter,
+}
+
+impl TryFrom<u8> for SlaveMode {
+    type Error = ();
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(Self::Idle),
+            1 => Ok(Self::Receiver),
+            2 => Ok(Self::GeneralCall),
+            3 => Ok(Self::Transmitter),
+            _ => Err(()),
+        }
+    }
+}
+
+/// Migrated in place of a `master_address` of `None`, slave addresses only
+/// have 7 bits.
+const NO_MASTER_ADDRESS: u8 = 0xff;
+
+#[repr(C)]
+#[derive(Debug, qemu_api_macros::offsets)]
 pub struct TWIRegisters {
     pub twbr: TWBR,
     pub twsr: TWSR,
     pub twar: TWAR,
     pub twdr: TWDR,
     pub twcr: TWCR,
+    pub twamr: TWAMR,
     pub in_transaction: bool,
+    /// Whether SLA+R was acknowledged, and the controller is in Master
+    /// Receiver mode.
+    pub master_receiver: bool,
+    /// Whether SLA+R was just sent. The next TWINT clear only completes the
+    /// address phase, bytes are received on the following ones.
+    pub sla_read_pending: bool,
+    /// Address of the slave in the current master transfer, kept across
+    /// repeated STARTs.
+    pub master_address: Option<u8>,
+    pub slave_mode: SlaveMode,
+    /// `master_address` as migrated, see [`pre_save`](Self::pre_save).
+    pub migration_master_address: u8,
+    /// `slave_mode` as migrated, see [`pre_save`](Self::pre_save).
+    pub migration_slave_mode: u8,
 }
 
 impl TWIRegisters {
@@ -36,6 +87,36 @@ pub fn reset(&mut self) {
         self.twar = 0b11111110.into();
         self.twdr = 0xFF.into();
         self.twcr = 0.into();
+        self.twamr = 0.into();
+        self.in_transaction = false;
+        self.master_receiver = false;
+        self.sla_read_pending = false;
+        self.master_address = None;
+        self.slave_mode = SlaveMode::Idle;
+    }
+
+    /// Set the status bits in TWSR, `status` is one of the `TW_*` codes.
+    ///
+    /// The prescaler bits are only written by software and are kept.
+    pub fn set_status(&mut self, status: u8) {
+        self.twsr.set_status(u5::new(status >> 3));
+    }
+
+    /// Store the fields that have no `VMState` in their migrated form.
+    pub fn pre_save(&mut self) {
+        self.migration_master_address = self.master_address.unwrap_or(NO_MASTER_ADDRESS);
+        self.migration_slave_mode = self.slave_mode as u8;
+    }
+
+    /// Restore the fields that have no `VMState` from their migrated form.
+    pub fn post_load(&mut self) -> Result<(), ()> {
+        self.master_address = match self.migration_master_address {
+            NO_MASTER_ADDRESS => None,
+            address if address <= 0x7f => Some(address),
+            _ => return Err(()),
+        };
+        self.slave_mode = SlaveMode::try_from(self.migration_slave_mode)?;
+        Ok(())
     }
 }
 
@@ -55,6 +136,7 @@ pub struct TWBR {
     pub twbr6: bool,
     pub twbr7: bool,
 }
+impl_vmstate_bitsized!(TWBR);
 
 #[bitsize(8)]
 #[derive(Clone, Copy, DebugBits, FromBits)]
@@ -76,6 +158,7 @@ pub struct TWSR {
     /// Serial Bus (Possible statuses are defined below as TW_\<STATUS\>)
     pub status: u5,
 }
+impl_vmstate_bitsized!(TWSR);
 
 #[bitsize(8)]
 #[derive(Clone, Copy, DebugBits, FromBits)]
@@ -100,6 +183,7 @@ pub struct TWAR {
     pub twa5: bool,
     pub twa6: bool,
 }
+impl_vmstate_bitsized!(TWAR);
 
 #[bitsize(8)]
 #[derive(Clone, Copy, DebugBits, FromBits)]
@@ -136,6 +220,7 @@ pub struct TWDR {
     pub twd6: bool,
     pub twd7: bool,
 }
+impl_vmstate_bitsized!(TWDR);
 
 #[bitsize(8)]
 #[derive(Clone, Copy, DebugBits, FromBits)]
@@ -220,6 +305,23 @@ pub struct TWCR {
     /// (TWDR) must be complete before clearing this flag.
     pub twint: bool,
 }
+impl_vmstate_bitsized!(TWCR);
+
+#[bitsize(8)]
+#[derive(Clone, Copy, DebugBits, FromBits)]
+/// TWAMR – TWI (Slave) Address Mask Register.
+///
+/// The TWAMR can be loaded with a 7-bit Slave Address mask. Each of the bits in
+/// TWAMR can mask (disable) the corresponding address bits in the TWI Address
+/// Register (TWAR). If the mask bit is set to one then the address match logic
+/// ignores the compare between the incoming address bit and the corresponding
+/// bit in TWAR.
+pub struct TWAMR {
+    /// This bit is reserved and will always read as zero.
+    _reserved: bool,
+    pub twam: u7,
+}
+impl_vmstate_bitsized!(TWAMR);
 
 // Start condition transmitted
 pub const TW_START: u8 = 0x08;
@@ -0,0 +1,169 @@
+//! The slave side of the TWI.
+//!
+//! A [`TWIState`] is a sysbus device, so it can't be addressed by other
+//! masters on its I2C bus. It owns a [`TWISlaveState`], an I2C slave at the
+//! address programmed in TWAR, which forwards the bus events to the Slave
+//! Receiver and Slave Transmitter state machines of the controller.
+
+use core::ptr::NonNull;
+use std::ffi::CStr;
+
+use qemu_api::{
+    bindings::{qdev_get_parent_bus, I2CBus, I2CNodeList, I2CSlave},
+    i2cslave::I2CSlaveImpl,
+    qdev::{DeviceImpl, DeviceState, ResettablePhasesImpl},
+    qom::{IsA, Object, ObjectImpl, ObjectType, ParentField},
+    qom_isa,
+    vmstate::VMStateDescription,
+};
+use qemu_api_macros::Object;
+
+use crate::device::TWIState;
+
+#[derive(Object, qemu_api_macros::offsets)]
+#[repr(C)]
+pub struct TWISlaveState {
+    pub parent_obj: ParentField<I2CSlave>,
+    /// The controller that owns this slave, set before realizing it.
+    pub twi: *const TWIState,
+}
+
+trait TWISlaveImpl: I2CSlaveImpl + IsA<TWISlaveState> {}
+
+impl TWISlaveImpl for TWISlaveState {}
+
+unsafe impl ObjectType for TWISlaveState {
+    type Class = TWISlaveClass;
+    const TYPE_NAME: &'static CStr = crate::TYPE_TWI_I2C_SLAVE;
+}
+
+impl ObjectImpl for TWISlaveState {
+    type ParentType = I2CSlave;
+
+    const INSTANCE_INIT: Option<unsafe fn(&mut Self)> = None;
+    const INSTANCE_POST_INIT: Option<fn(&Self)> = None;
+    const CLASS_INIT: fn(&mut Self::Class) = Self::Class::class_init::<Self>;
+}
+
+impl DeviceImpl for TWISlaveState {
+    fn vmsd() -> Option<&'static VMStateDescription> {
+        Some(&crate::device_class::VMSTATE_TWI_I2C_SLAVE)
+    }
+}
+impl ResettablePhasesImpl for TWISlaveState {}
+impl I2CSlaveImpl for TWISlaveState {}
+
+impl TWISlaveState {
+    fn twi(&self) -> &TWIState {
+        unsafe { self.twi.as_ref().expect("TWI slave without a controller") }
+    }
+
+    /// Whether the current transfer was started with the general call
+    /// address.
+    fn general_call(&self) -> bool {
+        unsafe {
+            let dev = (self as *const Self as *mut Self).cast();
+            let bus = qdev_get_parent_bus(dev).cast::<I2CBus>();
+            !bus.is_null() && (*bus).broadcast
+        }
+    }
+}
+
+qom_isa!(TWISlaveState: I2CSlave, DeviceState, Object);
+
+type MatchAndAddFn = unsafe extern "C" fn(*mut I2CSlave, u8, bool, *mut I2CNodeList) -> bool;
+
+#[repr(C)]
+pub struct TWISlaveClass {
+    parent_class: <I2CSlave as ObjectType>::Class,
+    /// The address matching of the I2C slave, which adds the slave to the
+    /// transfer.
+    parent_match_and_add: Option<MatchAndAddFn>,
+}
+
+impl TWISlaveClass {
+    fn class_init<T: TWISlaveImpl>(&mut self) {
+        self.parent_class.class_init::<T>();
+        unsafe {
+            let a = self as *mut TWISlaveClass;
+            let mut i2c = NonNull::new_unchecked(a.cast::<qemu_api::bindings::I2CSlaveClass>());
+            self.parent_match_and_add = i2c.as_ref().match_and_add;
+            i2c.as_mut().match_and_add = Some(twi_slave_match_and_add);
+            i2c.as_mut().recv = Some(twi_slave_recv);
+            i2c.as_mut().send = Some(twi_slave_send);
+            i2c.as_mut().send_async = Some(twi_slave_send_async);
+            i2c.as_mut().event = Some(twi_slave_event);
+            // Only created by the TWI controller.
+            i2c.as_mut().parent_class.user_creatable = false;
+        }
+    }
+}
+
+/// # Safety
+///
+/// We expect the FFI user of this function to pass valid pointers.
+pub unsafe extern "C" fn twi_slave_match_and_add(
+    candidate: *mut I2CSlave,
+    address: u8,
+    broadcast: bool,
+    current_devs: *mut I2CNodeList,
+) -> bool {
+    unsafe {
+        let slave =
+            NonNull::new(candidate.cast::<TWISlaveState>()).expect("I2Cslave pointer is null");
+        let class = &*(*candidate.cast::<Object>()).class.cast::<TWISlaveClass>();
+        let parent_match_and_add = class
+            .parent_match_and_add
+            .expect("I2C slave without match_and_add");
+
+        // Let the I2C core add the slave if the address matches TWAR under the
+        // TWAMR mask.
+        let address = if slave.as_ref().twi().slave_address_matches(address) {
+            (*candidate).address
+        } else {
+            address
+        };
+        parent_match_and_add(candidate, address, broadcast, current_devs)
+    }
+}
+
+/// # Safety
+///
+/// We expect the FFI user of this function to pass a valid pointer.
+pub unsafe extern "C" fn twi_slave_recv(dev: *mut I2CSlave) -> u8 {
+    unsafe {
+        let slave = NonNull::new(dev.cast::<TWISlaveState>()).expect("I2Cslave pointer is null");
+        slave.as_ref().twi().slave_recv()
+    }
+}
+
+/// # Safety
+///
+/// We expect the FFI user of this function to pass a valid pointer.
+pub unsafe extern "C" fn twi_slave_send(dev: *mut I2CSlave, data: u8) -> i32 {
+    unsafe {
+        let slave = NonNull::new(dev.cast::<TWISlaveState>()).expect("I2Cslave pointer is null");
+        slave.as_ref().twi().slave_send(data)
+    }
+}
+
+/// # Safety
+///
+/// We expect the FFI user of this function to pass a valid pointer.
+pub unsafe extern "C" fn twi_slave_send_async(dev: *mut I2CSlave, data: u8) {
+    unsafe {
+        let slave = NonNull::new(dev.cast::<TWISlaveState>()).expect("I2Cslave pointer is null");
+        slave.as_ref().twi().slave_send_async(data);
+    }
+}
+
+/// # Safety
+///
+/// We expect the FFI user of this function to pass a valid pointer.
+pub unsafe extern "C" fn twi_slave_event(dev: *mut I2CSlave, event: u32) -> i32 {
+    unsafe {
+        let slave = NonNull::new(dev.cast::<TWISlaveState>()).expect("I2Cslave pointer is null");
+        let slave = slave.as_ref();
+        slave.twi().slave_event(event, slave.general_call())
+    }
+}
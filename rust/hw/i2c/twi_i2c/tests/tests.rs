@@ -0,0 +1,71 @@
+use twi_i2c::registers::{SlaveMode, TWIRegisters, TW_MT_SLA_ACK, TW_START};
+
+/// Returns the registers in their reset state.
+fn registers() -> TWIRegisters {
+    let mut registers = TWIRegisters {
+        twbr: 0.into(),
+        twsr: 0.into(),
+        twar: 0.into(),
+        twdr: 0.into(),
+        twcr: 0.into(),
+        twamr: 0.into(),
+        in_transaction: false,
+        master_receiver: false,
+        sla_read_pending: false,
+        master_address: None,
+        slave_mode: SlaveMode::Idle,
+        migration_master_address: 0,
+        migration_slave_mode: 0,
+    };
+    registers.reset();
+    registers
+}
+
+#[test]
+fn it_keeps_the_prescaler_bits_when_setting_the_status() {
+    let mut registers = registers();
+    registers.twsr.set_twps0(true);
+    registers.twsr.set_twps1(true);
+
+    registers.set_status(TW_START);
+    assert_eq!(u8::from(registers.twsr), TW_START | 0b11);
+
+    registers.twsr.set_twps0(false);
+    registers.set_status(TW_MT_SLA_ACK);
+    assert_eq!(u8::from(registers.twsr), TW_MT_SLA_ACK | 0b10);
+}
+
+#[test]
+fn it_restores_the_migrated_transfer_state() {
+    let mut saved = registers();
+    saved.master_address = Some(0x3c);
+    saved.slave_mode = SlaveMode::Transmitter;
+    saved.pre_save();
+
+    let mut loaded = registers();
+    loaded.migration_master_address = saved.migration_master_address;
+    loaded.migration_slave_mode = saved.migration_slave_mode;
+    assert_eq!(loaded.post_load(), Ok(()));
+    assert_eq!(loaded.master_address, Some(0x3c));
+    assert_eq!(loaded.slave_mode, SlaveMode::Transmitter);
+
+    saved.master_address = None;
+    saved.pre_save();
+    loaded.migration_master_address = saved.migration_master_address;
+    assert_eq!(loaded.post_load(), Ok(()));
+    assert_eq!(loaded.master_address, None);
+}
+
+#[test]
+fn it_rejects_an_invalid_migrated_master_address() {
+    let mut registers = registers();
+    registers.migration_master_address = 0x80;
+    assert_eq!(registers.post_load(), Err(()));
+}
+
+#[test]
+fn it_rejects_an_invalid_migrated_slave_mode() {
+    let mut registers = registers();
+    registers.migration_slave_mode = 4;
+    assert_eq!(registers.post_load(), Err(()));
+}